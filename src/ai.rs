@@ -0,0 +1,171 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use serde_derive::{Deserialize, Serialize};
+use tcod::{colors, Map};
+
+use crate::{
+    combat,
+    game::is_blocked,
+    humanity,
+    log::{Category, Log},
+    objects::{self, Object, ObjectType},
+    path,
+};
+
+/// Energy a character spends on one action, a speed of 100 acts once per player turn.
+pub const TURN_ENERGY: i32 = 100;
+const SHUN_DISTANCE: i32 = 4;
+
+#[derive(PartialEq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub enum Behavior {
+    #[default]
+    Stay,
+    /// Roams around randomly, never farther than `radius` from home, anywhere when 0.
+    Wander {
+        radius: i32,
+    },
+    Follow,
+    Flee,
+}
+
+pub fn take_turns(
+    player: &Object,
+    map: &Map,
+    objects: &mut [Object],
+    rng: &mut Pcg32,
+    log: &mut Log,
+) {
+    for index in 0..objects.len() {
+        if objects[index].kind != ObjectType::Character {
+            continue;
+        }
+        objects[index].energy += objects[index].speed;
+        while objects[index].energy >= TURN_ENERGY {
+            objects[index].energy -= TURN_ENERGY;
+            act(player, map, objects, index, rng, log);
+        }
+    }
+}
+
+fn act(
+    player: &Object,
+    map: &Map,
+    objects: &mut [Object],
+    index: usize,
+    rng: &mut Pcg32,
+    log: &mut Log,
+) {
+    if objects[index].ally_turns > 0 && assist(objects, index, rng, log) {
+        return;
+    }
+    if humanity::shuns(&objects[index], player)
+        && distance(&objects[index], player.x, player.y) <= SHUN_DISTANCE.pow(2)
+    {
+        step_away(player, map, objects, index);
+        return;
+    }
+
+    match objects[index].behavior {
+        Behavior::Stay => {}
+        Behavior::Wander { radius } => {
+            let (dx, dy) = (rng.gen_range(-1, 2), rng.gen_range(-1, 2));
+            let object = &objects[index];
+            let (x, y) = (object.x + dx, object.y + dy);
+            let (home_x, home_y) = object.home;
+            if radius == 0 || (x - home_x).abs().max((y - home_y).abs()) <= radius {
+                step(player, map, objects, index, x, y);
+            }
+        }
+        Behavior::Follow => {
+            let from = (objects[index].x, objects[index].y);
+            if distance(&objects[index], player.x, player.y) > 2 {
+                if let Some((x, y)) = path::find(map, objects, from, (player.x, player.y))
+                    .and_then(|way| way.first().cloned())
+                {
+                    step(player, map, objects, index, x, y);
+                }
+            }
+        }
+        Behavior::Flee => {
+            step_away(player, map, objects, index);
+        }
+    }
+}
+
+/// Followers finish off whoever the player already hurt next to them.
+fn assist(objects: &mut [Object], index: usize, rng: &mut Pcg32, log: &mut Log) -> bool {
+    let (x, y) = (objects[index].x, objects[index].y);
+    let target = objects.iter().position(|object| {
+        object.kind == ObjectType::Character
+            && object.ally_turns == 0
+            && object.hp < object.max_hp
+            && (object.x - x).abs().max((object.y - y).abs()) == 1
+    });
+    let target = match target {
+        Some(target) => target,
+        None => return false,
+    };
+
+    let damage = combat::damage(&objects[index], &objects[target], rng);
+    objects[target].hp -= damage;
+    let message = if objects[target].hp <= 0 {
+        format!(
+            "{} takes the life of {}",
+            objects[index].description, objects[target].description
+        )
+    } else {
+        format!(
+            "{} hits {} for {}",
+            objects[index].description, objects[target].description, damage
+        )
+    };
+    log.add(Category::Combat, &message, colors::LIGHT_VIOLET);
+    if objects[target].hp <= 0 {
+        objects[target] = objects::garbage();
+    }
+    true
+}
+
+fn distance(object: &Object, x: i32, y: i32) -> i32 {
+    (object.x - x).pow(2) + (object.y - y).pow(2)
+}
+
+/// Characters never share a tile, not even with a chest or an open door.
+fn can_enter(player: &Object, map: &Map, objects: &[Object], x: i32, y: i32) -> bool {
+    (x != player.x || y != player.y)
+        && !is_blocked(x, y, map, objects)
+        && !objects.iter().any(|object| object.x == x && object.y == y)
+}
+
+fn step(player: &Object, map: &Map, objects: &mut [Object], index: usize, x: i32, y: i32) -> bool {
+    if !can_enter(player, map, objects, x, y) {
+        return false;
+    }
+    objects[index].x = x;
+    objects[index].y = y;
+    true
+}
+
+/// Steps to the free neighbour tile farthest from the player, false when cornered.
+pub fn step_away(player: &Object, map: &Map, objects: &mut [Object], index: usize) -> bool {
+    let (x, y) = (objects[index].x, objects[index].y);
+    let mut best = None;
+    let mut best_distance = distance(&objects[index], player.x, player.y);
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let (to_x, to_y) = (x + dx, y + dy);
+            if (dx, dy) == (0, 0) || !can_enter(player, map, objects, to_x, to_y) {
+                continue;
+            }
+            let to_distance = (to_x - player.x).pow(2) + (to_y - player.y).pow(2);
+            if to_distance > best_distance {
+                best = Some((to_x, to_y));
+                best_distance = to_distance;
+            }
+        }
+    }
+    match best {
+        Some((to_x, to_y)) => step(player, map, objects, index, to_x, to_y),
+        None => false,
+    }
+}
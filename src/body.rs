@@ -0,0 +1,53 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::objects::Object;
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Ability {
+    /// Jumps over whatever blocks the way when the tile behind it is free.
+    Hop,
+    /// Traders ask a quarter less.
+    Haggle,
+    Mute,
+}
+
+impl Ability {
+    pub fn name(self) -> &'static str {
+        match self {
+            Ability::Hop => "hop",
+            Ability::Haggle => "haggle",
+            Ability::Mute => "mute",
+        }
+    }
+}
+
+pub fn has(object: &Object, ability: Ability) -> bool {
+    object.abilities.contains(&ability)
+}
+
+pub fn haggled(player: &Object, price: i32) -> i32 {
+    if has(player, Ability::Haggle) {
+        price - price / 4
+    } else {
+        price
+    }
+}
+
+/// Belongings, humanity and behavior stay with the mind.
+pub fn swap(a: &mut Object, b: &mut Object) {
+    std::mem::swap(&mut a.x, &mut b.x);
+    std::mem::swap(&mut a.y, &mut b.y);
+    std::mem::swap(&mut a.ch, &mut b.ch);
+    std::mem::swap(&mut a.color, &mut b.color);
+    std::mem::swap(&mut a.description, &mut b.description);
+    std::mem::swap(&mut a.life_equivalent, &mut b.life_equivalent);
+    std::mem::swap(&mut a.hp, &mut b.hp);
+    std::mem::swap(&mut a.max_hp, &mut b.max_hp);
+    std::mem::swap(&mut a.power, &mut b.power);
+    std::mem::swap(&mut a.defense, &mut b.defense);
+    std::mem::swap(&mut a.speed, &mut b.speed);
+    std::mem::swap(&mut a.abilities, &mut b.abilities);
+    std::mem::swap(&mut a.original, &mut b.original);
+    a.home = (a.x, a.y);
+    b.home = (b.x, b.y);
+}
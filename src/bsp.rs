@@ -0,0 +1,119 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+
+use crate::{
+    map::{self, FloorGenerator, Stairs, TileMap},
+    Rect, Tile,
+};
+
+pub struct Bsp {
+    pub min_room: i32,
+    pub max_room: i32,
+}
+
+impl FloorGenerator for Bsp {
+    fn generate(&self, map: &mut TileMap, _floor_number: i32, rng: &mut Pcg32) -> Stairs {
+        let mut rooms = vec![];
+        for leaf in leaves(map, self.min_room, self.max_room, rng) {
+            let room = room_in(leaf, self.min_room, self.max_room, rng);
+            map::carve_room(map, room);
+            rooms.push(room);
+        }
+        connect(map, &rooms, rng)
+    }
+}
+
+/// Splits the map without its outer wall into pieces, each big enough for one room.
+pub fn leaves(map: &TileMap, min_room: i32, max_room: i32, rng: &mut Pcg32) -> Vec<Rect> {
+    let area = Rect::new(1, 1, map.len() as i32 - 2, map[0].len() as i32 - 2);
+    let mut leaves = vec![];
+    split(area, min_room, max_room, rng, &mut leaves);
+    leaves
+}
+
+/// Cuts the area in two until the pieces fit a room of `max_room` or cant be cut any more.
+/// Neighbouring leaves end up next to each other in the list.
+fn split(area: Rect, min_room: i32, max_room: i32, rng: &mut Pcg32, leaves: &mut Vec<Rect>) {
+    // a room keeps one tile of wall on each side
+    let min_leaf = min_room + 2;
+    let can_cut_x = area.w >= min_leaf * 2;
+    let can_cut_y = area.h >= min_leaf * 2;
+    let fits = area.w <= max_room + 2 && area.h <= max_room + 2;
+    if fits || (!can_cut_x && !can_cut_y) {
+        leaves.push(area);
+        return;
+    }
+
+    let cut_x = if can_cut_x && can_cut_y {
+        area.w > area.h || (area.w == area.h && rng.gen())
+    } else {
+        can_cut_x
+    };
+    if cut_x {
+        let cut = rng.gen_range(min_leaf, area.w - min_leaf + 1);
+        split(
+            Rect::new(area.x, area.y, cut, area.h),
+            min_room,
+            max_room,
+            rng,
+            leaves,
+        );
+        split(
+            Rect::new(area.x + cut, area.y, area.w - cut, area.h),
+            min_room,
+            max_room,
+            rng,
+            leaves,
+        );
+    } else {
+        let cut = rng.gen_range(min_leaf, area.h - min_leaf + 1);
+        split(
+            Rect::new(area.x, area.y, area.w, cut),
+            min_room,
+            max_room,
+            rng,
+            leaves,
+        );
+        split(
+            Rect::new(area.x, area.y + cut, area.w, area.h - cut),
+            min_room,
+            max_room,
+            rng,
+            leaves,
+        );
+    }
+}
+
+/// A random room inside the leaf, never touching its edges.
+pub fn room_in(leaf: Rect, min_room: i32, max_room: i32, rng: &mut Pcg32) -> Rect {
+    let w = random_size(leaf.w - 2, min_room, max_room, rng);
+    let h = random_size(leaf.h - 2, min_room, max_room, rng);
+    let x = leaf.x + 1 + rng.gen_range(0, leaf.w - 2 - w + 1);
+    let y = leaf.y + 1 + rng.gen_range(0, leaf.h - 2 - h + 1);
+    Rect::new(x, y, w, h)
+}
+
+fn random_size(space: i32, min_room: i32, max_room: i32, rng: &mut Pcg32) -> i32 {
+    let max = max_room.min(space).max(1);
+    rng.gen_range(min_room.min(max).max(1), max + 1)
+}
+
+/// Joins every room to the next one, the way down is in the first room and the way up
+/// in the last, the leaves keep them apart.
+pub fn connect(map: &mut TileMap, rooms: &[Rect], rng: &mut Pcg32) -> Stairs {
+    for pair in rooms.windows(2) {
+        map::carve_corridor(map, pair[0].center(), pair[1].center());
+    }
+
+    let down = rooms[0].center();
+    let up = if rooms.len() > 1 {
+        rooms[rooms.len() - 1].center()
+    } else {
+        map::far_floor(map, down, rng)
+    };
+    // a prefab may have a wall or a door right in its middle
+    for &(x, y) in &[down, up] {
+        map[x as usize][y as usize] = Tile::floor(x, y);
+    }
+    Stairs { up, down }
+}
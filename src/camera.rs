@@ -0,0 +1,97 @@
+/// The part of the floor the map panel shows. `x`, `y` is the map tile in its top left corner.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    pub margin: i32,
+}
+
+impl Camera {
+    pub fn new(width: i32, height: i32, margin: i32) -> Camera {
+        Camera {
+            x: 0,
+            y: 0,
+            width,
+            height,
+            margin,
+        }
+    }
+
+    /// Scrolls just enough to keep `x`, `y` the margin away from the edges of the view,
+    /// never showing anything past the edges of a map of `map_size`.
+    pub fn follow(&mut self, (x, y): (i32, i32), map_size: (i32, i32)) {
+        self.x = scroll(self.x, x, self.width, self.margin, map_size.0);
+        self.y = scroll(self.y, y, self.height, self.margin, map_size.1);
+    }
+
+    /// Where a map tile is drawn, none when it is out of view.
+    pub fn to_screen(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.on_screen(x - self.x, y - self.y)
+    }
+
+    /// Map tile under a cell of the window, none when the cell is not on the map panel.
+    pub fn to_world(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        self.on_screen(x, y).map(|(x, y)| (x + self.x, y + self.y))
+    }
+
+    fn on_screen(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            None
+        } else {
+            Some((x, y))
+        }
+    }
+}
+
+/// New offset along one axis, `position` is what the camera follows.
+fn scroll(offset: i32, position: i32, size: i32, margin: i32, map_size: i32) -> i32 {
+    // a margin over half the view would push the position out of it on the other side
+    let margin = margin.min((size - 1) / 2);
+    let mut offset = offset;
+    if position < offset + margin {
+        offset = position - margin;
+    }
+    if position > offset + size - 1 - margin {
+        offset = position - (size - 1 - margin);
+    }
+    offset.min(map_size - size).max(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scroll_waits_for_the_margin() {
+        assert_eq!(scroll(0, 20, 80, 10, 200), 0);
+        assert_eq!(scroll(0, 69, 80, 10, 200), 0);
+        assert_eq!(scroll(0, 75, 80, 10, 200), 6);
+        assert_eq!(scroll(50, 55, 80, 10, 200), 45);
+    }
+
+    #[test]
+    fn scroll_stays_on_the_map() {
+        assert_eq!(scroll(0, 199, 80, 10, 200), 120);
+        assert_eq!(scroll(30, 0, 80, 10, 200), 0);
+        // a map smaller than the view never scrolls
+        assert_eq!(scroll(0, 39, 80, 10, 40), 0);
+    }
+
+    #[test]
+    fn scroll_keeps_the_position_in_view_with_a_huge_margin() {
+        let offset = scroll(0, 100, 80, 50, 200);
+        assert!(offset <= 100 && 100 < offset + 80);
+    }
+
+    #[test]
+    fn screen_and_world_agree() {
+        let mut camera = Camera::new(80, 50, 10);
+        camera.follow((150, 100), (200, 120));
+        let (x, y) = camera.to_screen(150, 100).unwrap();
+        assert_eq!(camera.to_world(x, y), Some((150, 100)));
+        assert_eq!(camera.to_screen(0, 0), None);
+        assert_eq!(camera.to_world(80, 0), None);
+    }
+}
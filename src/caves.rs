@@ -0,0 +1,83 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use tcod::colors::WHITE;
+
+use crate::{
+    map::{self, FloorGenerator, Stairs, TileMap},
+    Tile,
+};
+
+pub struct Caves {
+    /// Percent of the tiles that start as rock.
+    pub fill: i32,
+    pub steps: i32,
+}
+
+impl FloorGenerator for Caves {
+    fn generate(&self, map: &mut TileMap, floor_number: i32, rng: &mut Pcg32) -> Stairs {
+        let (width, height) = (map.len(), map[0].len());
+        let border = border(width.min(height), floor_number);
+        let mut rock = vec![vec![true; height]; width];
+        for column in rock.iter_mut().take(width - border).skip(border) {
+            for cell in column.iter_mut().take(height - border).skip(border) {
+                *cell = rng.gen_range(0, 100) < self.fill;
+            }
+        }
+
+        // rock stays where most of the neighbourhood is rock, the rest crumbles
+        for _ in 0..self.steps {
+            let mut next = rock.clone();
+            for (x, column) in next.iter_mut().enumerate().take(width - 1).skip(1) {
+                for (y, cell) in column.iter_mut().enumerate().take(height - 1).skip(1) {
+                    *cell = rock_around(&rock, x, y) >= 5;
+                }
+            }
+            rock = next;
+        }
+
+        for x in 0..width {
+            for y in 0..height {
+                map[x][y] = if rock[x][y] {
+                    Tile::new(
+                        x as i32,
+                        y as i32,
+                        '#',
+                        WHITE,
+                        "Rough cave wall",
+                        false,
+                        false,
+                    )
+                } else {
+                    Tile::new(
+                        x as i32,
+                        y as i32,
+                        '.',
+                        WHITE,
+                        "Cave floor, damp and uneven",
+                        true,
+                        true,
+                    )
+                };
+            }
+        }
+        let down = map::far_floor(map, (0, 0), rng);
+        Stairs {
+            up: map::far_floor(map, down, rng),
+            down,
+        }
+    }
+}
+
+/// Solid rock around the caves, thicker on higher floors the way the tower narrows,
+/// but never taking more than a sixth of the map from each side.
+fn border(size: usize, floor_number: i32) -> usize {
+    (floor_number.max(1) as usize).min(size / 6).max(1)
+}
+
+/// Rock tiles in the 3x3 square around the tile, itself included.
+fn rock_around(rock: &[Vec<bool>], x: usize, y: usize) -> i32 {
+    rock[x - 1..=x + 1]
+        .iter()
+        .map(|column| column[y - 1..=y + 1].iter().filter(|rock| **rock).count() as i32)
+        .sum()
+}
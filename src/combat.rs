@@ -0,0 +1,73 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use tcod::{colors, Map};
+
+use crate::{
+    ai::{self, Behavior},
+    humanity,
+    log::{Category, Log},
+    objects::{Object, ObjectType},
+};
+
+pub fn max_hp(life_equivalent: i32) -> i32 {
+    life_equivalent * 2
+}
+
+pub fn damage(attacker: &Object, defender: &Object, rng: &mut Pcg32) -> i32 {
+    if attacker.power <= 0 {
+        return 0;
+    }
+    (rng.gen_range(1, attacker.power + 1) - defender.defense).max(0)
+}
+
+/// A hurt character hits back until its humanity tells it to run.
+pub fn react(
+    player: &mut Object,
+    map: &Map,
+    objects: &mut [Object],
+    index: usize,
+    rng: &mut Pcg32,
+    log: &mut Log,
+) {
+    if objects[index].kind != ObjectType::Character {
+        return;
+    }
+
+    if humanity::flees(&objects[index]) {
+        objects[index].behavior = Behavior::Flee;
+        if ai::step_away(player, map, objects, index) {
+            log.add(
+                Category::Combat,
+                &format!("{} flees in terror", objects[index].description),
+                colors::LIGHT_BLUE,
+            );
+        } else {
+            log.add(
+                Category::Combat,
+                &format!("{} is cornered and trembles", objects[index].description),
+                colors::LIGHT_BLUE,
+            );
+        }
+        return;
+    }
+
+    let object = &objects[index];
+    let damage = damage(object, player, rng);
+    player.hp -= damage;
+    if damage > 0 {
+        log.add(
+            Category::Combat,
+            &format!("{} hits you for {}", object.description, damage),
+            colors::ORANGE,
+        );
+    } else {
+        log.add(
+            Category::Combat,
+            &format!("{} fails to hurt you", object.description),
+            colors::DARKER_ORANGE,
+        );
+    }
+    if player.hp <= 0 {
+        log.add(Category::Combat, "Your life is taken", colors::RED);
+    }
+}
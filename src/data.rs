@@ -0,0 +1,329 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use serde_derive::Deserialize;
+use std::collections::HashMap;
+use tcod::Color;
+
+use crate::{
+    ai::{self, Behavior},
+    body::Ability,
+    combat,
+    inventory::Inventory,
+    life::LifeEffect,
+    map::Generator,
+    objects::{Object, ObjectType},
+    prefab::PrefabTable,
+    Item,
+};
+
+pub const DATA_DIR: &str = "data";
+
+/// Floors past the described ones it takes for one more loot roll.
+const DEPTH_PER_LOOT: i32 = 2;
+/// And for one more point of power, of defense.
+const DEPTH_PER_POWER: i32 = 2;
+const DEPTH_PER_DEFENSE: i32 = 3;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDef {
+    pub name: String,
+    pub gold: i32,
+    pub category: String,
+    /// Relative chance to be picked from a loot table, 0 never drops.
+    pub rarity: u32,
+    /// Floors the item can drop on, every floor when empty.
+    #[serde(default)]
+    pub floors: Vec<i32>,
+}
+
+impl ItemDef {
+    fn allowed_on(&self, floor: i32) -> bool {
+        self.floors.is_empty() || self.floors.contains(&floor)
+    }
+}
+
+/// Rolls between `min` and `max` items out of the named ones
+/// and everything in the listed categories.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LootTable {
+    pub min: i32,
+    pub max: i32,
+    #[serde(default)]
+    pub items: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ItemTable {
+    pub items: Vec<ItemDef>,
+    pub loot_tables: HashMap<String, LootTable>,
+}
+
+impl ItemTable {
+    fn validate(&self) -> Result<(), String> {
+        for (name, table) in &self.loot_tables {
+            if table.min < 0 || table.min > table.max {
+                return Err(format!("Loot table {} has a broken min/max", name));
+            }
+            for item in &table.items {
+                if !self.items.iter().any(|def| &def.name == item) {
+                    return Err(format!(
+                        "Loot table {} refers to unknown item {}",
+                        name, item
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rolls the table `extra` more times than it says.
+    pub fn roll(&self, table: &str, floor: i32, extra: i32, rng: &mut Pcg32) -> Inventory {
+        let table = self
+            .loot_tables
+            .get(table)
+            .unwrap_or_else(|| panic!("Unknown loot table {}", table));
+        let candidates = self
+            .items
+            .iter()
+            .filter(|def| {
+                table.items.contains(&def.name) || table.categories.contains(&def.category)
+            })
+            .filter(|def| def.allowed_on(floor) && def.rarity > 0)
+            .collect::<Vec<_>>();
+        let total = candidates.iter().fold(0, |sum, def| sum + def.rarity);
+
+        let mut content = Inventory::default();
+        if total == 0 {
+            return content;
+        }
+        for _ in 0..rng.gen_range(table.min, table.max + 1) + extra {
+            let mut roll = rng.gen_range(0, total);
+            for def in &candidates {
+                if roll < def.rarity {
+                    content.add(
+                        Item::Thing {
+                            description: def.name.clone(),
+                            gold: def.gold,
+                        },
+                        1,
+                    );
+                    break;
+                }
+                roll -= def.rarity;
+            }
+        }
+        content
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Template {
+    pub ch: char,
+    pub color: Color,
+    pub description: String,
+    pub kind: ObjectType,
+    pub humanity: i32,
+    /// Inclusive range, rolled for every spawned object.
+    pub life_equivalent: (i32, i32),
+    #[serde(default)]
+    pub power: i32,
+    #[serde(default)]
+    pub defense: i32,
+    #[serde(default)]
+    pub behavior: Behavior,
+    #[serde(default = "default_speed")]
+    pub speed: i32,
+    #[serde(default)]
+    pub abilities: Vec<Ability>,
+    #[serde(default)]
+    pub loot: Option<String>,
+}
+
+fn default_speed() -> i32 {
+    ai::TURN_ENERGY
+}
+
+/// Every free walkable tile gets the object with a chance of one in `one_in`,
+/// until there are `cap` of them on the floor.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Spawn {
+    pub template: String,
+    pub one_in: i32,
+    pub cap: i32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FloorSpawns {
+    #[serde(default)]
+    pub generator: Generator,
+    pub spawns: Vec<Spawn>,
+}
+
+/// Templates the code spawns by name, every data set has to have them.
+const REQUIRED_TEMPLATES: [&str; 5] = ["player", "door", "upstairs", "downstairs", "goal"];
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ObjectTable {
+    pub templates: HashMap<String, Template>,
+    pub floors: Vec<FloorSpawns>,
+    #[serde(default)]
+    /// Effects of a taken life by the template it came from.
+    pub life_uses: HashMap<String, Vec<LifeEffect>>,
+}
+
+impl ObjectTable {
+    fn validate(&self, items: &ItemTable) -> Result<(), String> {
+        for name in REQUIRED_TEMPLATES.iter() {
+            if !self.templates.contains_key(*name) {
+                return Err(format!("Template {} is missing", name));
+            }
+        }
+        for (name, template) in &self.templates {
+            if template.life_equivalent.0 > template.life_equivalent.1 {
+                return Err(format!("Template {} has a broken life_equivalent", name));
+            }
+            if let Some(loot) = &template.loot {
+                if !items.loot_tables.contains_key(loot) {
+                    return Err(format!(
+                        "Template {} refers to unknown loot table {}",
+                        name, loot
+                    ));
+                }
+            }
+        }
+        if self.floors.is_empty() {
+            return Err("At least one floor should be described".to_string());
+        }
+        for floor in &self.floors {
+            match floor.generator {
+                Generator::Bsp { min_room, max_room }
+                | Generator::Prefab { min_room, max_room }
+                    if min_room < 2 || max_room < min_room =>
+                {
+                    return Err(
+                        "Rooms should be at least 2 wide, max_room not below min_room".to_string(),
+                    );
+                }
+                Generator::Caves { fill, steps } if !(0..=100).contains(&fill) || steps < 0 => {
+                    return Err("Caves fill should be a percent and steps not negative".to_string());
+                }
+                _ => {}
+            }
+            for spawn in &floor.spawns {
+                if !self.templates.contains_key(&spawn.template) {
+                    return Err(format!("Floor spawns unknown template {}", spawn.template));
+                }
+                if spawn.one_in <= 0 {
+                    return Err(format!(
+                        "Spawn chance of {} should be positive",
+                        spawn.template
+                    ));
+                }
+            }
+        }
+        for name in self.life_uses.keys() {
+            if !self.templates.contains_key(name) {
+                return Err(format!("Life uses of unknown template {}", name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Population of the floor, floors above the described ones look like the last one.
+    pub fn floor(&self, floor: i32) -> &FloorSpawns {
+        let index = (floor.max(1) as usize - 1).min(self.floors.len() - 1);
+        &self.floors[index]
+    }
+
+    /// How many floors above the last described one the floor is.
+    pub fn depth(&self, floor: i32) -> i32 {
+        (floor - self.floors.len() as i32).max(0)
+    }
+}
+
+/// Everything designers can tweak without touching the code,
+/// loaded once at startup and never saved with the game.
+#[derive(Debug, Clone, Default)]
+pub struct Data {
+    pub items: ItemTable,
+    pub objects: ObjectTable,
+    pub prefabs: PrefabTable,
+}
+
+pub fn load_file<T: serde::de::DeserializeOwned>(dir: &str, name: &str) -> Result<T, String> {
+    let path = format!("{}/{}", dir, name);
+    let text =
+        std::fs::read_to_string(&path).map_err(|err| format!("Cant read {}: {}", path, err))?;
+    ron::de::from_str(&text).map_err(|err| format!("Cant parse {}: {}", path, err))
+}
+
+impl Data {
+    pub fn load(dir: &str) -> Result<Data, String> {
+        let items: ItemTable = load_file(dir, "items.ron")?;
+        items.validate()?;
+        let objects: ObjectTable = load_file(dir, "objects.ron")?;
+        objects.validate(&items)?;
+        let prefabs: PrefabTable = load_file(dir, "prefabs.ron")?;
+        prefabs.validate()?;
+
+        Ok(Data {
+            items,
+            objects,
+            prefabs,
+        })
+    }
+
+    /// Builds an object out of the named template, standing at `x`, `y` on `floor`.
+    pub fn spawn(&self, template: &str, x: i32, y: i32, floor: i32, rng: &mut Pcg32) -> Object {
+        let template = self
+            .objects
+            .templates
+            .get(template)
+            .unwrap_or_else(|| panic!("Unknown template {}", template));
+        // above the described floors the loot is what the last one drops, only more of it,
+        // and whoever lives there is tougher
+        let depth = self.objects.depth(floor);
+        let content = match &template.loot {
+            Some(loot) => self
+                .items
+                .roll(loot, floor - depth, depth / DEPTH_PER_LOOT, rng),
+            None => Inventory::default(),
+        };
+        let (min_life, max_life) = template.life_equivalent;
+        let mut life_equivalent = rng.gen_range(min_life, max_life + 1);
+        let (mut power, mut defense) = (template.power, template.defense);
+        if template.kind == ObjectType::Character {
+            life_equivalent += depth;
+            power += depth / DEPTH_PER_POWER;
+            defense += depth / DEPTH_PER_DEFENSE;
+        }
+
+        Object {
+            x,
+            y,
+            ch: template.ch,
+            humanity: template.humanity,
+            description: template.description.clone(),
+            color: template.color,
+            kind: template.kind,
+            content,
+            visited: false,
+            seen: false,
+            opened: false,
+            life_equivalent,
+            hp: combat::max_hp(life_equivalent),
+            max_hp: combat::max_hp(life_equivalent),
+            power,
+            defense,
+            behavior: template.behavior,
+            speed: template.speed,
+            energy: 0,
+            home: (x, y),
+            abilities: template.abilities.clone(),
+            original: false,
+            ally_turns: 0,
+        }
+    }
+}
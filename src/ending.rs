@@ -0,0 +1,112 @@
+use serde_derive::{Deserialize, Serialize};
+use tcod::{colors, console::Console, BackgroundFlag, RootConsole, TextAlignment};
+
+use crate::{game::GameState, humanity, objects::Object, panel};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stats {
+    /// Characters killed and lives bought.
+    pub lives_taken: i32,
+    pub body_swaps: i32,
+    pub trades: i32,
+    pub reached_goal: bool,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Ending {
+    Died,
+    Lost,
+    Devourer,
+    Stranger,
+    Innocent,
+    Survivor,
+}
+
+impl Ending {
+    /// The ending of a run that reached the goal.
+    pub fn choose(player: &Object, stats: &Stats) -> Ending {
+        if player.humanity <= humanity::CALLOUS {
+            Ending::Devourer
+        } else if !player.original {
+            Ending::Stranger
+        } else if stats.lives_taken == 0 && player.humanity >= humanity::HUMANE {
+            Ending::Innocent
+        } else {
+            Ending::Survivor
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Ending::Died => "Your life is taken",
+            Ending::Lost => "Nothing human is left",
+            Ending::Devourer => "The heart beats for a new master",
+            Ending::Stranger => "A stranger at the heart",
+            Ending::Innocent => "Clean hands",
+            Ending::Survivor => "Out of the dark",
+        }
+    }
+
+    pub fn text(self) -> &'static str {
+        match self {
+            Ending::Died => "The tower adds your bones to its walls.",
+            Ending::Lost => "You wander the floors as one more of its creatures.",
+            Ending::Devourer => "You take the heart of the tower and it takes you back.",
+            Ending::Stranger => "You reach the heart in a body that was never yours.",
+            Ending::Innocent => "You reach the heart without taking a single life.",
+            Ending::Survivor => "You reach the heart, poorer in gold and in soul.",
+        }
+    }
+}
+
+pub fn draw_summary(console: &mut RootConsole, state: &GameState, ending: Ending) {
+    let (width, height) = (60, 16);
+    let x = (console.width() - width) / 2;
+    let y = (console.height() - height) / 2;
+    panel(
+        console,
+        x,
+        y,
+        width,
+        height,
+        Some("The end"),
+        |panel, width, _| {
+            panel.set_default_foreground(colors::RED);
+            panel.print_ex(
+                width / 2,
+                2,
+                BackgroundFlag::Set,
+                TextAlignment::Center,
+                ending.title(),
+            );
+            panel.set_default_foreground(colors::WHITE);
+            panel.print_ex(
+                width / 2,
+                3,
+                BackgroundFlag::Set,
+                TextAlignment::Center,
+                ending.text(),
+            );
+
+            let lines = [
+                format!("Floor reached: {}", state.current_floor + 1),
+                format!("Turns: {}", state.log.turn()),
+                format!("Humanity left: {}", state.player.humanity),
+                format!("Lives taken: {}", state.stats.lives_taken),
+                format!("Bodies swapped: {}", state.stats.body_swaps),
+                format!("Deals signed: {}", state.stats.trades),
+                format!("Gold worth: {}", state.player.content.gold()),
+            ];
+            for (n, line) in lines.iter().enumerate() {
+                panel.print(4, n as i32 + 5, line);
+            }
+            panel.print_ex(
+                width / 2,
+                13,
+                BackgroundFlag::Set,
+                TextAlignment::Center,
+                "Press any key to continue",
+            );
+        },
+    );
+}
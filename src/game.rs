@@ -0,0 +1,1009 @@
+use crate::{
+    ai,
+    body::{self, Ability},
+    combat,
+    data::Data,
+    ending::{Ending, Stats},
+    humanity, life,
+    log::{Category, Log},
+    map::{self, make_map},
+    objects::{self, Object, ObjectType},
+    path,
+    reach::{self, Repairs},
+    settings::Settings,
+    trade, Item,
+};
+use rand::{seq::SliceRandom, Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{ser::SerializeStruct, Deserializer, Serializer};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tcod::{colors, map::FovAlgorithm, Map};
+
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Mode {
+    Walk,
+    Interact,
+    Attack,
+    Observe,
+    /// The run has ended, see `GameState::ending`.
+    Over,
+}
+
+/// Everything a player can ask the game to do, independent of the keys
+/// or the window that produced it.
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Command {
+    Direction(i32, i32),
+    Interact,
+    Attack,
+    Observe,
+    Confirm,
+    Cancel,
+    /// Walks to the nearest already seen object of the kind.
+    Travel(ObjectType),
+    TravelTo(i32, i32),
+    /// Picks a row of the trade or an inventory entry.
+    Select(usize),
+    /// Uses up a carried life.
+    Use,
+}
+
+fn walk(player: &mut Object, map: &Map, objects: &mut [Object], dx: i32, dy: i32, log: &mut Log) {
+    let x = player.x + dx;
+    let y = player.y + dy;
+
+    if body::has(player, Ability::Hop)
+        && is_blocked(x, y, map, objects)
+        && !is_blocked(x + dx, y + dy, map, objects)
+    {
+        player.x = x + dx;
+        player.y = y + dy;
+        return;
+    }
+
+    if !is_inside(map, x, y) || !map.is_walkable(x, y) {
+        return;
+    }
+
+    if let Some(object) = objects
+        .iter()
+        .find(|object| !object.is_walkable() && object.x == x && object.y == y)
+    {
+        log.add(
+            Category::System,
+            &format!(
+                "You cant pass {}{}",
+                object.description.chars().next().unwrap().to_lowercase(),
+                &object.description[1..]
+            ),
+            colors::DARKER_RED,
+        );
+        return;
+    }
+
+    player.x = x;
+    player.y = y;
+}
+
+/// Nothing can step on walls, outside the map or onto non walkable objects.
+pub fn is_inside(map: &Map, x: i32, y: i32) -> bool {
+    let (width, height) = map.size();
+    x >= 0 && x < width && y >= 0 && y < height
+}
+
+pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
+    !is_inside(map, x, y)
+        || !map.is_walkable(x, y)
+        || objects
+            .iter()
+            .any(|object| object.x == x && object.y == y && !object.is_walkable())
+}
+
+pub fn get_object(x: i32, y: i32, objects: &mut [Object]) -> Option<&mut Object> {
+    objects
+        .iter_mut()
+        .find(|object| object.x == x && object.y == y)
+}
+
+fn garbage_colect(objects: &mut Vec<Object>) {
+    objects.retain(|object| object.kind != ObjectType::Garbage);
+}
+
+pub struct Floor {
+    pub map: Map,
+    pub objects: Vec<Object>,
+    pub tile_map: map::TileMap,
+}
+
+impl Floor {
+    /// Builds the tcod map out of the tiles and the doors standing on them.
+    pub fn new(tile_map: map::TileMap, objects: Vec<Object>) -> Floor {
+        let mut map = Map::new(tile_map.len() as i32, tile_map[0].len() as i32);
+        for row in tile_map.iter() {
+            for tile_entity in row.iter() {
+                map.set(
+                    tile_entity.x,
+                    tile_entity.y,
+                    tile_entity.transparent,
+                    tile_entity.walkable,
+                )
+            }
+        }
+        for object in objects.iter() {
+            if object.kind == ObjectType::Door && object.opened {
+                map.set(object.x, object.y, true, true);
+            }
+        }
+
+        Floor {
+            map,
+            tile_map,
+            objects,
+        }
+    }
+    /// Where the stair of the kind stands, the goal takes the place of the last way up.
+    pub fn stair(&self, kind: ObjectType) -> Option<(i32, i32)> {
+        self.objects
+            .iter()
+            .find(|object| {
+                object.kind == kind
+                    || (kind == ObjectType::UpStair && object.kind == ObjectType::Goal)
+            })
+            .map(|object| (object.x, object.y))
+    }
+
+    /// The free tile closest to `x`, `y`, where someone arriving there can stand.
+    pub fn free_near(&self, x: i32, y: i32) -> (i32, i32) {
+        let (width, height) = self.map.size();
+        for radius in 1..width.max(height) {
+            for dx in -radius..=radius {
+                for dy in -radius..=radius {
+                    let (near_x, near_y) = (x + dx, y + dy);
+                    if dx.abs().max(dy.abs()) == radius
+                        && !is_blocked(near_x, near_y, &self.map, &self.objects)
+                        && !self
+                            .objects
+                            .iter()
+                            .any(|object| object.x == near_x && object.y == near_y)
+                    {
+                        return (near_x, near_y);
+                    }
+                }
+            }
+        }
+        (x, y)
+    }
+}
+
+impl serde::Serialize for Floor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut floor = serializer.serialize_struct("Floor", 2)?;
+        floor.serialize_field("tile_map", &self.tile_map)?;
+        floor.serialize_field("objects", &self.objects)?;
+        floor.end()
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Floor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Floor, D::Error> {
+        #[derive(Deserialize)]
+        struct FloorData {
+            tile_map: map::TileMap,
+            objects: Vec<Object>,
+        }
+
+        let data: FloorData = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Floor::new(data.tile_map, data.objects))
+    }
+}
+
+/// Every floor gets its own generator derived from the world seed,
+/// so a floor looks the same no matter what happened before it was built.
+fn floor_rng(seed: u64, n: i32) -> Pcg32 {
+    Pcg32::seed_from_u64(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ n as u64)
+}
+
+fn make_floor(n: i32, data: &Data, settings: &Settings, rng: &mut Pcg32) -> (Floor, Repairs) {
+    let mut objects = vec![];
+    let (tile_map, stairs, repairs) = make_map(
+        &mut objects,
+        settings.width as usize,
+        settings.height as usize,
+        n,
+        data,
+        rng,
+    );
+
+    // the last floor has nowhere to go up, its goal is right there instead
+    let up = if settings.is_top(n) {
+        "goal"
+    } else {
+        "upstairs"
+    };
+    objects.push(data.spawn(up, stairs.up.0, stairs.up.1, n, rng));
+    objects.push(data.spawn("downstairs", stairs.down.0, stairs.down.1, n, rng));
+
+    let reachable = reach::reachable(&tile_map, stairs.down);
+    let mut cells = vec![];
+    for tile_row in tile_map.iter() {
+        for tile in tile_row.iter() {
+            if tile.walkable
+                && reachable[tile.x as usize][tile.y as usize]
+                && get_object(tile.x, tile.y, &mut objects).is_none()
+            {
+                cells.push((tile.x, tile.y));
+            }
+        }
+    }
+    // Caps would crowd everything into the first rows otherwise.
+    cells.shuffle(rng);
+
+    let spawns = &data.objects.floor(n).spawns;
+    let mut spawned = vec![0; spawns.len()];
+    for (x, y) in cells {
+        for (spawn, count) in spawns.iter().zip(spawned.iter_mut()) {
+            if *count < spawn.cap && rng.gen_range(0, spawn.one_in) == 0 {
+                objects.push(data.spawn(&spawn.template, x, y, n, rng));
+                *count += 1;
+                break;
+            }
+        }
+    }
+    (Floor::new(tile_map, objects), repairs)
+}
+
+/// Any free walkable tile the way down can be reached from.
+fn start_position(floor: &Floor, rng: &mut Pcg32) -> (i32, i32) {
+    let (down_x, down_y) = floor.stair(ObjectType::DownStair).unwrap_or((0, 0));
+    let reachable = reach::reachable(&floor.tile_map, (down_x, down_y));
+    let cells = floor
+        .tile_map
+        .iter()
+        .flatten()
+        .filter(|tile| {
+            tile.walkable
+                && reachable[tile.x as usize][tile.y as usize]
+                && !floor
+                    .objects
+                    .iter()
+                    .any(|object| object.x == tile.x && object.y == tile.y)
+        })
+        .map(|tile| (tile.x, tile.y))
+        .collect::<Vec<_>>();
+    match cells.choose(rng) {
+        Some(&cell) => cell,
+        None => floor.free_near(down_x, down_y),
+    }
+}
+
+/// Builds every floor for `runs` seeds in a row and tells how often
+/// the generators left something cut off that had to be repaired.
+pub fn floor_stats(data: &Data, settings: &Settings, first_seed: u64, runs: u64) -> String {
+    let mut report = String::new();
+    for n in 1..=settings.floors as i32 {
+        let mut repaired = 0;
+        let mut total = Repairs::default();
+        for seed in first_seed..first_seed + runs {
+            let (_, repairs) = make_floor(n, data, settings, &mut floor_rng(seed, n));
+            if repairs.needed() {
+                repaired += 1;
+            }
+            total.doors += repairs.doors;
+            total.sealed += repairs.sealed;
+            total.regenerated += repairs.regenerated;
+        }
+        report += &format!(
+            "Floor {} ({:?}): {}/{} repaired, {} doors carved, {} regions sealed, {} regenerated\n",
+            n,
+            data.objects.floor(n).generator,
+            repaired,
+            runs,
+            total.doors,
+            total.sealed,
+            total.regenerated
+        );
+    }
+    report
+}
+
+/// The whole game without any window attached: feed it commands with `step`
+/// and read the fields to draw it.
+#[derive(Serialize, Deserialize)]
+pub struct GameState {
+    pub seed: u64,
+    pub settings: Settings,
+    /// Floors the player has been to, the next one is built on the way up.
+    pub floors: Vec<Floor>,
+    pub player: Object,
+    pub mode: Mode,
+    pub trade: trade::Trade,
+    pub current_floor: usize,
+    pub observe_x: i32,
+    pub observe_y: i32,
+    pub selected_item: Option<usize>,
+    pub stats: Stats,
+    pub ending: Option<Ending>,
+    /// Followers whose time ran out, the last one comes back first.
+    pub fallen: Vec<Object>,
+    /// Turns the torch keeps burning brighter.
+    pub torch: i32,
+    /// Saved next to the state, so replays can be checked without it.
+    #[serde(skip)]
+    pub log: Log,
+    #[serde(skip)]
+    pub data: Data,
+    rng: Pcg32,
+}
+
+impl GameState {
+    pub fn new(seed: u64, data: Data, settings: Settings) -> GameState {
+        let (first_floor, _) = make_floor(1, &data, &settings, &mut floor_rng(seed, 1));
+
+        let mut log = Log::default();
+        log.add(
+            Category::System,
+            "You entered the tower of darkness",
+            colors::GREEN,
+        );
+        log.add(
+            Category::System,
+            "Your torch is going to fade out",
+            colors::GREY,
+        );
+        log.add(
+            Category::System,
+            "And your mind as well",
+            colors::DARKER_GREY,
+        );
+        if settings.endless {
+            log.add(
+                Category::System,
+                "They say this tower has no top",
+                colors::LIGHTER_GREY,
+            );
+        } else {
+            log.add(
+                Category::System,
+                "You know exactly that your goal",
+                colors::LIGHTER_GREY,
+            );
+            log.add(
+                Category::System,
+                "       is on the last floor",
+                colors::LIGHTER_GREY,
+            );
+        }
+        log.add(
+            Category::System,
+            &format!("World seed {}", seed),
+            colors::DARKER_GREY,
+        );
+
+        let mut rng = Pcg32::seed_from_u64(seed);
+        let (x, y) = start_position(&first_floor, &mut rng);
+        let mut player = data.spawn("player", x, y, 1, &mut rng);
+        player.original = true;
+
+        let mut state = GameState {
+            seed,
+            settings,
+            floors: vec![first_floor],
+            player,
+            mode: Mode::Walk,
+            trade: trade::Trade::default(),
+            current_floor: 0,
+            observe_x: 0,
+            observe_y: 1,
+            selected_item: None,
+            stats: Stats::default(),
+            ending: None,
+            fallen: vec![],
+            torch: 0,
+            log,
+            data,
+            rng,
+        };
+        state.compute_fov();
+        state
+    }
+
+    /// Everyone else gets to act, followers and the torch burn down.
+    fn pass_turn(&mut self) {
+        let floor = &mut self.floors[self.current_floor];
+        ai::take_turns(
+            &self.player,
+            &floor.map,
+            &mut floor.objects,
+            &mut self.rng,
+            &mut self.log,
+        );
+        for object in floor
+            .objects
+            .iter_mut()
+            .filter(|object| object.ally_turns > 0)
+        {
+            object.ally_turns -= 1;
+            if object.ally_turns == 0 {
+                self.log.add(
+                    Category::Combat,
+                    &format!("{} falls, its borrowed life runs out", object.description),
+                    colors::DARK_VIOLET,
+                );
+                self.fallen
+                    .push(std::mem::replace(object, objects::garbage()));
+            }
+        }
+        self.torch = (self.torch - 1).max(0);
+        self.log.next_turn();
+    }
+
+    /// Builds the current floor the first time the player reaches it, it stays as left after that.
+    fn build_floor(&mut self) {
+        while self.floors.len() <= self.current_floor {
+            let n = self.floors.len() as i32 + 1;
+            let (floor, _) =
+                make_floor(n, &self.data, &self.settings, &mut floor_rng(self.seed, n));
+            self.floors.push(floor);
+        }
+    }
+
+    /// Puts the player next to the stair of the kind on the current floor.
+    fn arrive(&mut self, kind: ObjectType) {
+        let floor = &self.floors[self.current_floor];
+        if let Some((x, y)) = floor.stair(kind) {
+            let (x, y) = floor.free_near(x, y);
+            self.player.x = x;
+            self.player.y = y;
+        }
+    }
+
+    /// Uses the selected life, or the first one carried when nothing is selected.
+    /// True when it did something and took the turn.
+    fn use_life(&mut self) -> bool {
+        let selected = self
+            .selected_item
+            .and_then(|n| self.player.content.get(n))
+            .filter(|stack| stack.item.is_life());
+        let stack = match selected.or_else(|| {
+            self.player
+                .content
+                .iter()
+                .find(|stack| stack.item.is_life())
+        }) {
+            Some(stack) => stack.clone(),
+            None => {
+                self.log.add(
+                    Category::Loot,
+                    "You carry no life to use",
+                    colors::DARK_GREY,
+                );
+                return false;
+            }
+        };
+
+        if let Item::Life { kind, description } = &stack.item {
+            let target = life::Target {
+                player: &mut self.player,
+                floor: &mut self.floors[self.current_floor],
+                fallen: &mut self.fallen,
+                torch: &mut self.torch,
+                floor_number: self.current_floor as i32 + 1,
+                data: &self.data,
+                rng: &mut self.rng,
+                log: &mut self.log,
+            };
+            if life::use_life(*kind, description, target) {
+                self.player.content.remove(&stack.item, 1);
+                self.selected_item = None;
+                return true;
+            }
+            self.log.add(
+                Category::Loot,
+                "Nothing comes of that life here",
+                colors::DARK_GREY,
+            );
+        }
+        false
+    }
+
+    fn attack(&mut self, dx: i32, dy: i32) {
+        let floor = &mut self.floors[self.current_floor];
+        let x = self.player.x + dx;
+        let y = self.player.y + dy;
+
+        if floor.map.is_walkable(x, y) == false {
+            self.log.add(
+                Category::Combat,
+                "There is no life in this wall",
+                colors::LIGHT_BLUE,
+            );
+            return;
+        }
+        let index = floor
+            .objects
+            .iter()
+            .position(|object| object.x == x && object.y == y);
+        if let Some(index) = index {
+            let object = &mut floor.objects[index];
+            if !object.is_attackable() {
+                self.log.add(
+                    Category::Combat,
+                    &format!("{} has no life to take", object.description),
+                    colors::LIGHT_BLUE,
+                );
+                return;
+            }
+
+            let damage = combat::damage(&self.player, object, &mut self.rng);
+            object.hp -= damage;
+            if object.hp > 0 {
+                if damage > 0 {
+                    self.log.add(
+                        Category::Combat,
+                        &format!("You hit {} for {}", object.description, damage),
+                        colors::LIGHT_BLUE,
+                    );
+                } else {
+                    self.log.add(
+                        Category::Combat,
+                        &format!("{} shrugs off your blow", object.description),
+                        colors::LIGHT_BLUE,
+                    );
+                }
+                combat::react(
+                    &mut self.player,
+                    &floor.map,
+                    &mut floor.objects,
+                    index,
+                    &mut self.rng,
+                    &mut self.log,
+                );
+                return;
+            }
+
+            self.log.add(
+                Category::Combat,
+                &format!("{} life taken", object.description),
+                colors::LIGHT_BLUE,
+            );
+            self.log.add(
+                Category::Combat,
+                "Your mind cant stand this level of violence",
+                colors::RED,
+            );
+            if object.kind == ObjectType::Chest {
+                self.log
+                    .add(Category::Combat, "PURE INNOCENT CHEST!11", colors::RED);
+                self.log.add(
+                    Category::Combat,
+                    "Humanity decreased for nothing",
+                    colors::RED,
+                );
+            } else {
+                self.log
+                    .add(Category::Combat, "Humanity decreased", colors::RED);
+                self.stats.lives_taken += 1;
+                self.player.content.add(
+                    Item::Life {
+                        kind: object.kind,
+                        description: object.description.clone(),
+                    },
+                    1,
+                );
+            }
+
+            self.player.humanity -= 1;
+
+            std::mem::replace(object, objects::garbage());
+            return;
+        }
+        self.log.add(
+            Category::Combat,
+            "You beat the air in panic",
+            colors::LIGHT_RED,
+        );
+    }
+
+    fn interact(&mut self, dx: i32, dy: i32) {
+        let floor = &mut self.floors[self.current_floor];
+        let x = self.player.x + dx;
+        let y = self.player.y + dy;
+
+        if let Some(object) = get_object(x, y, &mut floor.objects) {
+            match object.kind {
+                ObjectType::Chest if object.visited => {
+                    self.log.add(
+                        Category::Loot,
+                        "You desperately opens the same chest again",
+                        colors::LIGHTER_RED,
+                    );
+                    self.log
+                        .add(Category::Loot, "Still nothing", colors::LIGHTER_RED);
+                }
+                ObjectType::Chest => {
+                    self.log.add(
+                        Category::Loot,
+                        "You open a chest and start looting",
+                        colors::GREEN,
+                    );
+                    if object.content.is_empty() {
+                        self.log
+                            .add(Category::Loot, "Loot fairy says no", colors::RED);
+                    } else {
+                        for loot in object.content.iter() {
+                            self.log.add(
+                                Category::Loot,
+                                &format!("you got {} x{}", loot.item.description(), loot.count),
+                                colors::DARKER_GREY,
+                            );
+                            self.player.content.add(loot.item.clone(), loot.count);
+                        }
+                    }
+                    object.visited = true;
+                }
+                ObjectType::Character => {
+                    let index = floor
+                        .objects
+                        .iter()
+                        .position(|object| object.x == x && object.y == y);
+                    if let Some(index) = index {
+                        let trader = &floor.objects[index];
+                        if body::has(&self.player, Ability::Mute) {
+                            self.log.add(
+                                Category::Trade,
+                                "You try to speak, but only a croak comes out",
+                                colors::DARK_RED,
+                            );
+                        } else if humanity::refuses(trader, &self.player) {
+                            self.log.add(
+                                Category::Trade,
+                                &format!(
+                                    "{} refuses to deal with the likes of you",
+                                    trader.description
+                                ),
+                                colors::DARK_RED,
+                            );
+                        } else {
+                            self.trade.open(index, trader, &self.player);
+                        }
+                    }
+                }
+                ObjectType::Door => {
+                    drop(object);
+
+                    let mut doors = vec![];
+                    let mut visited = std::collections::HashSet::new();
+                    visited.insert((x, y));
+                    doors.push((x, y));
+                    while doors.len() != 0 {
+                        let door = doors.pop().unwrap();
+                        let deltas = [
+                            (1, 0),
+                            (-1, 0),
+                            (0, 1),
+                            (0, -1),
+                            (1, 1),
+                            (1, -1),
+                            (-1, 1),
+                            (-1, -1),
+                        ];
+                        for (dx, dy) in &deltas {
+                            let pos = (door.0 + dx, door.1 + dy);
+                            if let Some(object) = get_object(pos.0, pos.1, &mut floor.objects) {
+                                if visited.contains(&pos) == false
+                                    && object.kind == ObjectType::Door
+                                {
+                                    visited.insert(pos);
+                                    doors.push(pos);
+                                }
+                            }
+                        }
+                        if let Some(object) = get_object(door.0, door.1, &mut floor.objects) {
+                            object.opened ^= true;
+                            floor
+                                .map
+                                .set(object.x, object.y, object.opened, object.opened);
+                        }
+                    }
+                }
+                ObjectType::UpStair => {
+                    if !self.settings.is_top(self.current_floor as i32 + 1) {
+                        let gold = self.player.content.gold();
+                        if gold < 7 {
+                            self.log.add(
+                                Category::System,
+                                "Your cost should be more than 7 gold",
+                                colors::DARK_RED,
+                            );
+                            self.log.add(
+                                Category::System,
+                                &format!("You cost {}", gold),
+                                colors::DARK_RED,
+                            );
+                        } else {
+                            self.player.content.clear();
+                            self.current_floor += 1;
+                            self.log.add(
+                                Category::System,
+                                "All your item sacrificied to the door",
+                                colors::LIGHTER_RED,
+                            );
+                            self.log.add(
+                                Category::System,
+                                "You ascended to the next level of the tower",
+                                colors::LIGHTER_RED,
+                            );
+                        }
+                    } else {
+                        self.log.add(
+                            Category::System,
+                            "That stair is broken and buried with rocks",
+                            colors::DARK_GREY,
+                        );
+                    }
+                }
+                ObjectType::DownStair => {
+                    if self.current_floor > 0 {
+                        self.current_floor -= 1;
+                        self.log.add(
+                            Category::System,
+                            "You escaped back in panic.",
+                            colors::DARK_RED,
+                        );
+                    } else {
+                        self.log.add(
+                            Category::System,
+                            "That stair is broken and buried with rocks",
+                            colors::DARK_GREY,
+                        );
+                    }
+                }
+                ObjectType::Goal => {
+                    self.log.add(
+                        Category::System,
+                        "You lay your hands on the heart of the tower",
+                        colors::RED,
+                    );
+                    self.stats.reached_goal = true;
+                }
+                ObjectType::Garbage => {}
+            }
+        }
+    }
+
+    fn end(&mut self, ending: Ending) {
+        self.log.add(Category::System, ending.title(), colors::RED);
+        self.mode = Mode::Over;
+        self.ending = Some(ending);
+    }
+
+    pub fn floor(&self) -> &Floor {
+        &self.floors[self.current_floor]
+    }
+
+    fn compute_fov(&mut self) {
+        let floor = &mut self.floors[self.current_floor];
+        floor.map.compute_fov(
+            self.player.x,
+            self.player.y,
+            self.settings.view_radius
+                + if self.torch > 0 {
+                    life::TORCH_RADIUS
+                } else {
+                    0
+                },
+            true,
+            FovAlgorithm::Basic,
+        );
+        for object in floor.objects.iter_mut() {
+            if floor.map.is_in_fov(object.x, object.y) {
+                object.seen = true;
+            }
+        }
+    }
+
+    /// Everything worth stopping for the player can see right now.
+    fn in_sight(&self) -> HashSet<usize> {
+        let floor = self.floor();
+        floor
+            .objects
+            .iter()
+            .enumerate()
+            .filter(|(_, object)| {
+                object.kind != ObjectType::Garbage
+                    && object.kind != ObjectType::Door
+                    && floor.map.is_in_fov(object.x, object.y)
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Walks the player towards `x`, `y` turn by turn until something new shows up.
+    fn travel(&mut self, x: i32, y: i32) {
+        let floor = &self.floors[self.current_floor];
+        let way = match path::find(
+            &floor.map,
+            &floor.objects,
+            (self.player.x, self.player.y),
+            (x, y),
+        ) {
+            Some(way) => way,
+            None => {
+                self.log
+                    .add(Category::System, "You see no way there", colors::DARK_GREY);
+                return;
+            }
+        };
+
+        let in_sight = self.in_sight();
+        let hp = self.player.hp;
+        for (x, y) in way {
+            let floor = &mut self.floors[self.current_floor];
+            if is_blocked(x, y, &floor.map, &floor.objects) {
+                break;
+            }
+            self.player.x = x;
+            self.player.y = y;
+            self.pass_turn();
+            self.compute_fov();
+
+            if self.player.hp < hp || !self.in_sight().is_subset(&in_sight) {
+                self.log.add(
+                    Category::System,
+                    "You stop, something comes into view",
+                    colors::DARK_GREY,
+                );
+                break;
+            }
+        }
+    }
+
+    pub fn step(&mut self, command: Command) {
+        if self.mode == Mode::Over {
+            return;
+        }
+
+        let floor_before = self.current_floor;
+        let floor = &mut self.floors[self.current_floor];
+
+        if self.trade.is_opened() {
+            self.trade.step(
+                command,
+                &mut self.player,
+                &mut floor.objects,
+                &mut self.log,
+                &mut self.stats,
+            );
+        } else {
+            let mut direction = None;
+            let mut acted = false;
+            let mut travel = None;
+            let mut use_life = false;
+            match command {
+                Command::Direction(dx, dy) => {
+                    direction = Some((dx, dy));
+                }
+                Command::Interact => {
+                    self.mode = Mode::Interact;
+                }
+                Command::Attack => {
+                    self.mode = Mode::Attack;
+                }
+                Command::Observe if self.mode == Mode::Observe => {
+                    self.mode = Mode::Walk;
+                }
+                Command::Observe => {
+                    self.mode = Mode::Observe;
+                    self.observe_x = self.player.x;
+                    self.observe_y = self.player.y;
+                }
+                Command::Confirm if self.mode == Mode::Observe => {
+                    self.mode = Mode::Walk;
+                    travel = Some((self.observe_x, self.observe_y));
+                }
+                Command::Confirm | Command::Cancel => {
+                    self.mode = Mode::Walk;
+                }
+                Command::Travel(kind) => {
+                    self.mode = Mode::Walk;
+                    let (x, y) = (self.player.x, self.player.y);
+                    travel = floor
+                        .objects
+                        .iter()
+                        .filter(|object| object.kind == kind && object.seen)
+                        .min_by_key(|object| (object.x - x).pow(2) + (object.y - y).pow(2))
+                        .map(|object| (object.x, object.y));
+                    if travel.is_none() {
+                        self.log.add(
+                            Category::System,
+                            "You have not seen anything like that here",
+                            colors::DARK_GREY,
+                        );
+                    }
+                }
+                Command::TravelTo(x, y) => {
+                    self.mode = Mode::Walk;
+                    if is_inside(&floor.map, x, y) {
+                        travel = Some((x, y));
+                    }
+                }
+                Command::Use => {
+                    self.mode = Mode::Walk;
+                    use_life = true;
+                }
+                Command::Select(n) => {
+                    self.selected_item =
+                        if n < self.player.content.len() && self.selected_item != Some(n) {
+                            Some(n)
+                        } else {
+                            None
+                        };
+                }
+            }
+
+            if let Some((dx, dy)) = direction {
+                match self.mode {
+                    Mode::Walk => {
+                        walk(
+                            &mut self.player,
+                            &floor.map,
+                            &mut floor.objects,
+                            dx,
+                            dy,
+                            &mut self.log,
+                        );
+                        self.mode = Mode::Walk;
+                        acted = true;
+                    }
+                    Mode::Attack => {
+                        self.attack(dx, dy);
+                        self.mode = Mode::Walk;
+                        acted = true;
+                    }
+                    Mode::Interact => {
+                        self.interact(dx, dy);
+                        self.mode = Mode::Walk;
+                        acted = true;
+                    }
+                    Mode::Over => {}
+                    Mode::Observe => {
+                        let (width, height) = floor.map.size();
+                        self.observe_x = (self.observe_x + dx).max(0).min(width - 1);
+                        self.observe_y = (self.observe_y + dy).max(0).min(height - 1);
+                    }
+                }
+            }
+
+            // stairs lead to the linked stair of the floor left behind
+            if self.current_floor > floor_before {
+                self.build_floor();
+                self.arrive(ObjectType::DownStair);
+            } else if self.current_floor < floor_before {
+                self.arrive(ObjectType::UpStair);
+            }
+            if let Some((x, y)) = travel {
+                self.travel(x, y);
+            }
+            if use_life {
+                acted = self.use_life();
+            }
+
+            // talking to a trader stops the time until the deal is done
+            if acted && !self.trade.is_opened() {
+                self.pass_turn();
+            }
+        }
+
+        if self.player.hp <= 0 {
+            self.end(Ending::Died);
+        } else if self.player.humanity <= 0 {
+            self.end(Ending::Lost);
+        } else if self.stats.reached_goal {
+            self.end(Ending::choose(&self.player, &self.stats));
+        }
+
+        garbage_colect(&mut self.floors[self.current_floor].objects);
+        self.compute_fov();
+    }
+}
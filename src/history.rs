@@ -0,0 +1,127 @@
+use tcod::{
+    colors,
+    console::Console,
+    input::{Key, KeyCode, Mouse},
+    BackgroundFlag, RootConsole, TextAlignment,
+};
+
+use crate::{
+    game::Command,
+    log::{Category, Entry, Log, CATEGORIES},
+    panel,
+};
+
+/// Full screen view of the whole log, scrolled from the newest entries and filtered by category.
+#[derive(Default)]
+pub struct History {
+    opened: bool,
+    /// Entries hidden below the bottom of the view.
+    scroll: usize,
+    hidden: Vec<Category>,
+}
+
+impl History {
+    pub fn toggle(&mut self) {
+        self.opened ^= true;
+        self.scroll = 0;
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.opened
+    }
+
+    fn visible<'a>(&self, log: &'a Log) -> Vec<&'a Entry> {
+        log.entries()
+            .iter()
+            .filter(|entry| !self.hidden.contains(&entry.category))
+            .collect()
+    }
+
+    fn scroll_by(&mut self, log: &Log, lines: i32, page: i32) {
+        let max = (self.visible(log).len() as i32 - page).max(0);
+        self.scroll = (self.scroll as i32 + lines).max(0).min(max) as usize;
+    }
+
+    pub fn draw(&self, console: &mut RootConsole, log: &Log) {
+        if !self.opened {
+            return;
+        }
+
+        let page = console.height() - 5;
+        panel(
+            console,
+            0,
+            0,
+            console.width(),
+            console.height(),
+            Some("History"),
+            |panel, width, _| {
+                let filters = CATEGORIES
+                    .iter()
+                    .enumerate()
+                    .map(|(n, category)| {
+                        let mark = if self.hidden.contains(category) {
+                            ' '
+                        } else {
+                            'x'
+                        };
+                        format!("{} [{}] {}", n + 1, mark, category.name())
+                    })
+                    .collect::<Vec<_>>();
+                panel.print(1, 1, filters.join("   "));
+                panel.print_ex(
+                    width - 2,
+                    1,
+                    BackgroundFlag::Set,
+                    TextAlignment::Right,
+                    "PgUp/PgDn - scroll, Esc - close",
+                );
+
+                let visible = self.visible(log);
+                let bottom = visible.len() - self.scroll.min(visible.len());
+                let top = bottom.saturating_sub(page as usize);
+                for (n, entry) in visible[top..bottom].iter().enumerate() {
+                    panel.set_default_foreground(entry.color);
+                    panel.print(
+                        1,
+                        n as i32 + 3,
+                        format!("{:>5} {}", entry.turn, entry.text()),
+                    );
+                }
+                panel.set_default_foreground(colors::WHITE);
+            },
+        );
+    }
+
+    /// Keys bound to walking scroll, digits toggle the categories, any other bound key closes.
+    pub fn step(&mut self, console: &RootConsole, log: &Log, key: Key, command: Option<Command>) {
+        let page = console.height() - 5;
+        match (key.code, key.printable, command) {
+            (KeyCode::PageUp, ..) => self.scroll_by(log, page, page),
+            (KeyCode::PageDown, ..) => self.scroll_by(log, -page, page),
+            (_, _, Some(Command::Direction(0, -1))) => self.scroll_by(log, 1, page),
+            (_, _, Some(Command::Direction(0, 1))) => self.scroll_by(log, -1, page),
+            (_, digit @ '1'..='4', _) => {
+                let category = CATEGORIES[digit as usize - '1' as usize];
+                if let Some(index) = self.hidden.iter().position(|hidden| *hidden == category) {
+                    self.hidden.remove(index);
+                } else {
+                    self.hidden.push(category);
+                }
+                self.scroll = 0;
+            }
+            (_, _, None) => {}
+            _ => self.toggle(),
+        }
+    }
+
+    pub fn wheel(&mut self, console: &RootConsole, log: &Log, mouse: Mouse) {
+        let page = console.height() - 5;
+        if mouse.wheel_up {
+            self.scroll_by(log, 3, page);
+        }
+        if mouse.wheel_down {
+            self.scroll_by(log, -3, page);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use crate::objects::Object;
+
+/// At this much humanity or less anyone counts as callous.
+pub const CALLOUS: i32 = 2;
+/// At this much humanity or more anyone counts as humane, the player starts right here.
+pub const HUMANE: i32 = 5;
+/// Traders this much more humane than the player refuse to deal at all.
+pub const REFUSAL_GAP: i32 = 6;
+/// What a trader pays for a piece of humanity.
+pub const HUMANITY_GOLD: i32 = 4;
+
+pub fn refuses(trader: &Object, player: &Object) -> bool {
+    trader.humanity - player.humanity >= REFUSAL_GAP
+}
+
+/// Every piece of humanity lost below humane adds a fifth to the price,
+/// unless the trader has lost even more.
+pub fn markup(trader: &Object, player: &Object, price: i32) -> i32 {
+    if trader.humanity <= player.humanity {
+        return price;
+    }
+    let lost = (HUMANE - player.humanity).max(0);
+    price + (price * lost + 4) / 5
+}
+
+/// Nobody wants the life of someone with nothing human left in it.
+pub fn can_offer_life(player: &Object) -> bool {
+    player.humanity > CALLOUS
+}
+
+/// Only once it started to slip away traders get to buy the rest.
+pub fn can_sell_humanity(player: &Object) -> bool {
+    player.humanity < HUMANE
+}
+
+/// Humane characters keep away from callous players.
+pub fn shuns(character: &Object, player: &Object) -> bool {
+    character.humanity >= HUMANE && player.humanity <= CALLOUS
+}
+
+/// Callous characters fight to the end, humane ones run as soon as half their health is gone.
+pub fn flees(character: &Object) -> bool {
+    if character.humanity <= CALLOUS {
+        false
+    } else if character.humanity >= HUMANE {
+        character.hp * 2 < character.max_hp
+    } else {
+        character.hp * 3 < character.max_hp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects;
+
+    fn with_humanity(humanity: i32) -> Object {
+        Object {
+            humanity,
+            ..objects::garbage()
+        }
+    }
+
+    #[test]
+    fn markup_only_from_more_humane_traders() {
+        assert_eq!(markup(&with_humanity(1), &with_humanity(3), 10), 10);
+        assert_eq!(markup(&with_humanity(3), &with_humanity(3), 10), 10);
+        assert_eq!(markup(&with_humanity(9), &with_humanity(HUMANE), 10), 10);
+    }
+
+    #[test]
+    fn markup_grows_with_lost_humanity() {
+        assert_eq!(markup(&with_humanity(5), &with_humanity(4), 10), 12);
+        assert_eq!(markup(&with_humanity(5), &with_humanity(3), 10), 14);
+        assert_eq!(markup(&with_humanity(5), &with_humanity(0), 10), 20);
+        // rounded up, a single gold still costs more
+        assert_eq!(markup(&with_humanity(5), &with_humanity(4), 1), 2);
+    }
+}
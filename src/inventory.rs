@@ -0,0 +1,106 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::Item;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stack {
+    pub item: Item,
+    pub count: i32,
+}
+
+/// Items grouped into stacks of identical things, in the order they were first got.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Inventory {
+    stacks: Vec<Stack>,
+}
+
+impl Inventory {
+    pub fn add(&mut self, item: Item, count: i32) {
+        if count <= 0 {
+            return;
+        }
+        match self.stacks.iter_mut().find(|stack| stack.item == item) {
+            Some(stack) => stack.count += count,
+            None => self.stacks.push(Stack { item, count }),
+        }
+    }
+
+    /// Takes up to `count` of `item` away, returns how many were actually taken.
+    pub fn remove(&mut self, item: &Item, count: i32) -> i32 {
+        let index = match self.stacks.iter().position(|stack| &stack.item == item) {
+            Some(index) => index,
+            None => return 0,
+        };
+        let taken = count.min(self.stacks[index].count);
+        self.stacks[index].count -= taken;
+        if self.stacks[index].count <= 0 {
+            self.stacks.remove(index);
+        }
+        taken
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Stack> {
+        self.stacks.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Stack> {
+        self.stacks.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.is_empty()
+    }
+
+    pub fn gold(&self) -> i32 {
+        self.stacks
+            .iter()
+            .fold(0, |sum, stack| sum + stack.item.gold() * stack.count)
+    }
+
+    pub fn clear(&mut self) {
+        self.stacks.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thing(description: &str) -> Item {
+        Item::Thing {
+            description: description.to_string(),
+            gold: 2,
+        }
+    }
+
+    #[test]
+    fn add_stacks_identical_items() {
+        let mut inventory = Inventory::default();
+        inventory.add(thing("frog leg"), 2);
+        inventory.add(thing("old coin"), 1);
+        inventory.add(thing("frog leg"), 3);
+        inventory.add(thing("dust"), 0);
+        assert_eq!(inventory.len(), 2);
+        assert_eq!(inventory.get(0).unwrap().count, 5);
+        assert_eq!(inventory.get(1).unwrap().item, thing("old coin"));
+        assert_eq!(inventory.gold(), 12);
+    }
+
+    #[test]
+    fn remove_takes_what_there_is() {
+        let mut inventory = Inventory::default();
+        inventory.add(thing("frog leg"), 3);
+        inventory.add(thing("old coin"), 1);
+        assert_eq!(inventory.remove(&thing("frog leg"), 2), 2);
+        assert_eq!(inventory.get(0).unwrap().count, 1);
+        assert_eq!(inventory.remove(&thing("frog leg"), 5), 1);
+        assert_eq!(inventory.remove(&thing("dust"), 1), 0);
+        // empty stacks are gone, the rest keeps its order
+        assert_eq!(inventory.len(), 1);
+        assert_eq!(inventory.get(0).unwrap().item, thing("old coin"));
+    }
+}
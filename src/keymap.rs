@@ -0,0 +1,184 @@
+use serde_derive::Deserialize;
+use tcod::input::{Key, KeyCode, KeyCode::*};
+
+use crate::{data, game::Command, objects::ObjectType};
+
+/// Names usable in keys.ron besides single printable characters.
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("Up", Up),
+    ("Down", Down),
+    ("Left", Left),
+    ("Right", Right),
+    ("Enter", Enter),
+    ("Esc", Escape),
+    ("Space", Spacebar),
+    ("Tab", Tab),
+    ("Backspace", Backspace),
+    ("Home", Home),
+    ("End", End),
+    ("PageUp", PageUp),
+    ("PageDown", PageDown),
+    ("NumPad0", NumPad0),
+    ("NumPad1", NumPad1),
+    ("NumPad2", NumPad2),
+    ("NumPad3", NumPad3),
+    ("NumPad4", NumPad4),
+    ("NumPad5", NumPad5),
+    ("NumPad6", NumPad6),
+    ("NumPad7", NumPad7),
+    ("NumPad8", NumPad8),
+    ("NumPad9", NumPad9),
+    ("NumPadEnter", NumPadEnter),
+];
+
+#[derive(Deserialize)]
+struct KeymapFile {
+    commands: Vec<(Vec<String>, Command)>,
+    save: Vec<String>,
+    history: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Binding {
+    Code(KeyCode),
+    Char(char),
+}
+
+impl Binding {
+    fn parse(name: &str) -> Result<Binding, String> {
+        if let Some((_, code)) = NAMED_KEYS.iter().find(|(named, _)| *named == name) {
+            return Ok(Binding::Code(*code));
+        }
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Ok(Binding::Char(ch)),
+            _ => Err(format!("Unknown key \"{}\" in keys.ron", name)),
+        }
+    }
+
+    fn matches(self, key: Key) -> bool {
+        match self {
+            Binding::Code(code) => key.code == code,
+            Binding::Char(ch) => key.printable == ch,
+        }
+    }
+
+    fn name(self) -> String {
+        match self {
+            Binding::Code(Up) => tcod::chars::ARROW_N.to_string(),
+            Binding::Code(Down) => tcod::chars::ARROW_S.to_string(),
+            Binding::Code(Left) => tcod::chars::ARROW_W.to_string(),
+            Binding::Code(Right) => tcod::chars::ARROW_E.to_string(),
+            Binding::Code(code) => {
+                let name = NAMED_KEYS
+                    .iter()
+                    .find(|(_, named)| *named == code)
+                    .map_or("?", |(name, _)| name);
+                name.replace("NumPad", "kp")
+            }
+            Binding::Char(ch) => ch.to_string(),
+        }
+    }
+}
+
+/// Which key does what, loaded from keys.ron in the data directory.
+pub struct Keymap {
+    commands: Vec<(Vec<Binding>, Command)>,
+    save: Vec<Binding>,
+    history: Vec<Binding>,
+}
+
+fn parse_all(names: &[String]) -> Result<Vec<Binding>, String> {
+    names.iter().map(|name| Binding::parse(name)).collect()
+}
+
+fn describe(command: Command) -> &'static str {
+    match command {
+        Command::Direction(0, -1) => "north",
+        Command::Direction(0, 1) => "south",
+        Command::Direction(-1, 0) => "west",
+        Command::Direction(1, 0) => "east",
+        Command::Direction(-1, -1) => "north west",
+        Command::Direction(1, -1) => "north east",
+        Command::Direction(-1, 1) => "south west",
+        Command::Direction(1, 1) => "south east",
+        Command::Direction(_, _) => "step",
+        Command::Interact => "interact",
+        Command::Attack => "violently take life",
+        Command::Observe => "toggle observe mode",
+        Command::Confirm => "confirm / travel",
+        Command::Cancel => "cancel",
+        Command::Travel(ObjectType::UpStair) => "travel upstairs",
+        Command::Travel(ObjectType::DownStair) => "travel downstairs",
+        Command::Travel(_) | Command::TravelTo(_, _) => "travel",
+        Command::Use => "use a life",
+        Command::Select(_) => "select",
+    }
+}
+
+impl Keymap {
+    pub fn load(dir: &str) -> Result<Keymap, String> {
+        let file: KeymapFile = data::load_file(dir, "keys.ron")?;
+        Ok(Keymap {
+            commands: file
+                .commands
+                .iter()
+                .map(|(names, command)| Ok((parse_all(names)?, *command)))
+                .collect::<Result<_, String>>()?,
+            save: parse_all(&file.save)?,
+            history: parse_all(&file.history)?,
+        })
+    }
+
+    /// None for unbound keys, like a bare Shift pressed on the way to a capital letter.
+    pub fn command(&self, key: Key) -> Option<Command> {
+        self.commands
+            .iter()
+            .find(|(bindings, _)| bindings.iter().any(|binding| binding.matches(key)))
+            .map(|(_, command)| *command)
+    }
+
+    pub fn saves(&self, key: Key) -> bool {
+        self.save.iter().any(|binding| binding.matches(key))
+    }
+
+    pub fn opens_history(&self, key: Key) -> bool {
+        self.history.iter().any(|binding| binding.matches(key))
+    }
+
+    /// Every key bound to the command, to show it in hints.
+    pub fn keys_for(&self, command: Command) -> String {
+        self.commands
+            .iter()
+            .filter(|(_, bound)| *bound == command)
+            .flat_map(|(bindings, _)| bindings.iter().map(|binding| binding.name()))
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Help lines in the order of keys.ron, like "k/kp8 - north".
+    pub fn help(&self) -> Vec<String> {
+        let mut help = self
+            .commands
+            .iter()
+            .map(|(bindings, command)| {
+                let keys = bindings
+                    .iter()
+                    .map(|binding| binding.name())
+                    .collect::<Vec<_>>();
+                format!("{} - {}", keys.join("/"), describe(*command))
+            })
+            .collect::<Vec<_>>();
+        for (bindings, what) in &[
+            (&self.save, "save the game"),
+            (&self.history, "message history"),
+        ] {
+            let keys = bindings
+                .iter()
+                .map(|binding| binding.name())
+                .collect::<Vec<_>>();
+            help.push(format!("{} - {}", keys.join("/"), what));
+        }
+        help
+    }
+}
@@ -0,0 +1,165 @@
+use rand_pcg::Pcg32;
+use serde_derive::Deserialize;
+use tcod::colors;
+
+use crate::{
+    ai::Behavior,
+    data::Data,
+    game::{is_blocked, Floor},
+    log::{Category, Log},
+    objects::{Object, ObjectType},
+};
+
+/// Torch burning on someone else's life sees this much farther.
+pub const TORCH_RADIUS: i32 = 8;
+
+/// What using a taken life does, objects.ron lists them per template
+/// and the first one that can do anything is used.
+#[derive(PartialEq, Debug, Clone, Deserialize)]
+pub enum LifeEffect {
+    Humanity(i32),
+    /// Only while hurt.
+    Heal(i32),
+    Torch {
+        turns: i32,
+    },
+    /// A follower from the same template for a while, needs a free tile next to the player.
+    Summon {
+        turns: i32,
+    },
+    /// Brings back the last companion that ran out of time, if there is one.
+    Revive {
+        turns: i32,
+    },
+}
+
+pub struct Target<'a> {
+    pub player: &'a mut Object,
+    pub floor: &'a mut Floor,
+    pub fallen: &'a mut Vec<Object>,
+    pub torch: &'a mut i32,
+    pub floor_number: i32,
+    pub data: &'a Data,
+    pub rng: &'a mut Pcg32,
+    pub log: &'a mut Log,
+}
+
+/// False when none of the effects could do anything.
+pub fn use_life(kind: ObjectType, description: &str, target: Target) -> bool {
+    let source = source_template(target.data, kind, description);
+    let effects = match source
+        .as_ref()
+        .and_then(|source| target.data.objects.life_uses.get(source))
+    {
+        Some(effects) => effects,
+        None => return false,
+    };
+    let Target {
+        player,
+        floor,
+        fallen,
+        torch,
+        floor_number,
+        data,
+        rng,
+        log,
+    } = target;
+
+    for effect in effects {
+        match *effect {
+            LifeEffect::Humanity(amount) => {
+                player.humanity += amount;
+                log.add(
+                    Category::Loot,
+                    "You feel a little more human",
+                    colors::GREEN,
+                );
+                return true;
+            }
+            LifeEffect::Heal(amount) if player.hp < player.max_hp => {
+                player.hp = (player.hp + amount).min(player.max_hp);
+                log.add(
+                    Category::Loot,
+                    "Borrowed life closes your wounds",
+                    colors::GREEN,
+                );
+                return true;
+            }
+            LifeEffect::Heal(_) => {}
+            LifeEffect::Torch { turns } => {
+                *torch += turns;
+                log.add(Category::Loot, "Your torch flares up", colors::LIGHT_YELLOW);
+                return true;
+            }
+            LifeEffect::Summon { turns } => {
+                let place = free_neighbour(player, floor);
+                // nobody gets a second player to follow them around
+                let template = source.as_ref().filter(|source| *source != "player");
+                if let (Some((x, y)), Some(template)) = (place, template) {
+                    let mut ally = data.spawn(template, x, y, floor_number, rng);
+                    ally.content.clear();
+                    ally.behavior = Behavior::Follow;
+                    ally.ally_turns = turns;
+                    log.add(
+                        Category::Loot,
+                        &format!("{} rises to follow you", ally.description),
+                        colors::LIGHT_VIOLET,
+                    );
+                    floor.objects.push(ally);
+                    return true;
+                }
+            }
+            LifeEffect::Revive { turns } => {
+                if let (Some((x, y)), false) = (free_neighbour(player, floor), fallen.is_empty()) {
+                    let mut ally = fallen.pop().unwrap();
+                    ally.x = x;
+                    ally.y = y;
+                    ally.hp = ally.max_hp;
+                    ally.ally_turns = turns;
+                    log.add(
+                        Category::Loot,
+                        &format!("{} comes back to you", ally.description),
+                        colors::LIGHT_VIOLET,
+                    );
+                    floor.objects.push(ally);
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+fn free_neighbour(player: &Object, floor: &Floor) -> Option<(i32, i32)> {
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            let (x, y) = (player.x + dx, player.y + dy);
+            if (dx, dy) != (0, 0)
+                && !is_blocked(x, y, &floor.map, &floor.objects)
+                && !floor
+                    .objects
+                    .iter()
+                    .any(|object| object.x == x && object.y == y)
+            {
+                return Some((x, y));
+            }
+        }
+    }
+    None
+}
+
+/// The template the life came from, or any other of the same kind when it is gone from the data.
+fn source_template(data: &Data, kind: ObjectType, description: &str) -> Option<String> {
+    let mut names = data
+        .objects
+        .templates
+        .iter()
+        .filter(|(_, template)| template.kind == kind)
+        .collect::<Vec<_>>();
+    names.sort_by_key(|(name, _)| *name);
+    names
+        .iter()
+        .find(|(_, template)| template.description == description)
+        .or_else(|| names.iter().find(|(name, _)| *name != "player"))
+        .map(|(name, _)| name.to_string())
+}
@@ -1,23 +1,105 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{fs::File, io::Write};
 use tcod::colors::Color;
 
-static mut LOGS: Option<Vec<(String, Color)>> = None;
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Category {
+    Combat,
+    Trade,
+    Loot,
+    System,
+}
+
+pub const CATEGORIES: [Category; 4] = [
+    Category::Combat,
+    Category::Trade,
+    Category::Loot,
+    Category::System,
+];
+
+impl Category {
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Combat => "combat",
+            Category::Trade => "trade",
+            Category::Loot => "loot",
+            Category::System => "system",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub turn: u32,
+    pub category: Category,
+    pub message: String,
+    pub color: Color,
+    /// How many times in a row the same message came.
+    pub count: u32,
+}
 
-pub fn log(message: &str, color: Color) {
-    unsafe {
-        if LOGS.is_none() {
-            LOGS = Some(vec![]);
+impl Entry {
+    pub fn text(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.message, self.count)
+        } else {
+            self.message.clone()
         }
-        LOGS.as_mut().unwrap().push((message.into(), color));
     }
 }
 
-pub fn logs<'a>() -> &'a [(String, Color)] {
-    unsafe {
-        if LOGS.is_none() {
-            LOGS = Some(vec![]);
+/// Everything that happened in the game, saved along with it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Log {
+    entries: Vec<Entry>,
+    turn: u32,
+    #[serde(skip)]
+    file: Option<File>,
+}
+
+impl Log {
+    pub fn add(&mut self, category: Category, message: &str, color: Color) {
+        if let Some(file) = &mut self.file {
+            // losing the copy on disk is not worth stopping the game
+            let _ = writeln!(file, "[{}] {}: {}", self.turn, category.name(), message);
         }
 
-        //LOGS.lock().unwrap()
-        LOGS.as_ref().unwrap()
+        if let Some(last) = self.entries.last_mut() {
+            if last.category == category && last.message == message {
+                last.count += 1;
+                last.turn = self.turn;
+                return;
+            }
+        }
+        self.entries.push(Entry {
+            turn: self.turn,
+            category,
+            message: message.to_string(),
+            color,
+            count: 1,
+        });
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn turn(&self) -> u32 {
+        self.turn
+    }
+
+    pub fn next_turn(&mut self) {
+        self.turn += 1;
+    }
+
+    /// Appends every message from now on to a text file as well.
+    pub fn append_to(&mut self, path: &str) -> Result<(), String> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("Cant open {}: {}", path, err))?;
+        self.file = Some(file);
+        Ok(())
     }
 }
@@ -1,25 +1,55 @@
 mod map;
 
 use tcod::colors::{BLACK, GREY, WHITE};
-use tcod::map::FovAlgorithm;
 
 use tcod::{
-    colors, console, console::*, input::KeyCode::*, input::*, BackgroundFlag, Color, Map,
+    colors, console, console::*, input::KeyCode::*, input::*, BackgroundFlag, Color,
     OffscreenConsole, RootConsole,
 };
 
 use crate::{
-    map::make_map,
+    camera::Camera,
+    data::Data,
+    ending::Ending,
+    game::{Command, Floor, GameState, Mode},
+    history::History,
+    keymap::Keymap,
+    log::Category,
+    morgue::Score,
     objects::{Object, ObjectType},
+    settings::{Screen, Settings},
 };
 use noise::*;
+use serde_derive::{Deserialize, Serialize};
 use tcod::chars::BLOCK1;
 
+mod ai;
+mod body;
+mod bsp;
+mod camera;
+mod caves;
+mod combat;
+mod data;
+mod ending;
+mod game;
+mod history;
+mod humanity;
+mod inventory;
+mod keymap;
+mod life;
 mod log;
+mod morgue;
 mod objects;
+mod path;
+mod prefab;
+mod reach;
+mod replay;
+mod save;
+mod settings;
+mod tower;
 mod trade;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Tile {
     x: i32,
     y: i32,
@@ -63,6 +93,18 @@ impl Tile {
         }
     }
 
+    pub fn floor(x: i32, y: i32) -> Self {
+        Tile {
+            x,
+            y,
+            ch: '.',
+            color: WHITE,
+            description: String::from("Floor, you can step on it"),
+            walkable: true,
+            transparent: true,
+        }
+    }
+
     pub fn wall() -> Self {
         Tile {
             walkable: false,
@@ -76,29 +118,73 @@ impl Tile {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Rect {
-    description: String,
-    walkable: bool,
-    transparent: bool,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect { x, y, w, h }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        (self.x + self.w / 2, self.y + self.h / 2)
+    }
+
+    /// Column and row of a screen cell within a panel drawn over the rect, none on or
+    /// outside its border.
+    pub fn inside(&self, x: i32, y: i32) -> Option<(i32, i32)> {
+        if x > self.x && x < self.x + self.w - 1 && y > self.y && y < self.y + self.h - 1 {
+            Some((x - self.x, y - self.y))
+        } else {
+            None
+        }
+    }
 }
 
-pub const FIELD_WIDTH: i32 = 80;
-pub const FIELD_HEIGHT: i32 = 80;
 pub const INFO_WIDTH: i32 = 45;
-pub const HELP_HEIGHT: i32 = 5;
-pub const VIEW_RADIUS: f64 = 20.;
-pub const FLOORS: usize = 3;
-
-#[derive(PartialEq, Debug, Clone, Copy)]
-enum Mode {
-    Walk,
-    Interact,
-    Attack,
-    Observe,
+pub const HELP_HEIGHT: i32 = 6;
+pub const FPS: i32 = 30;
+/// The keybindings need this much width, the side panels this much height.
+const MIN_FIELD_WIDTH: i32 = 80;
+const MIN_FIELD_HEIGHT: i32 = 50;
+/// Panel row of the first inventory entry.
+const INVENTORY_FIRST_ROW: i32 = 1;
+
+/// Where the parts of the window go, the field shows as much of the floor as the screen
+/// settings ask for but is never smaller than the panels around it need.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    pub field_width: i32,
+    pub field_height: i32,
+}
+
+impl Layout {
+    pub fn new(screen: &Screen) -> Layout {
+        Layout {
+            field_width: screen.field_width.max(MIN_FIELD_WIDTH),
+            field_height: screen.field_height.max(MIN_FIELD_HEIGHT),
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.field_width + INFO_WIDTH
+    }
+
+    pub fn height(&self) -> i32 {
+        self.field_height + HELP_HEIGHT
+    }
+
+    pub fn inventory(&self) -> Rect {
+        Rect::new(self.field_width, 20, INFO_WIDTH, 20)
+    }
 }
 
-#[derive(Clone, Debug, PartialEq, Hash, Eq)]
+#[derive(Clone, Debug, PartialEq, Hash, Eq, Serialize, Deserialize)]
 pub enum Item {
     Thing {
         description: String,
@@ -111,6 +197,13 @@ pub enum Item {
 }
 
 impl Item {
+    pub fn is_life(&self) -> bool {
+        match self {
+            Item::Life { .. } => true,
+            Item::Thing { .. } => false,
+        }
+    }
+
     fn description(&self) -> String {
         match self {
             Item::Thing {
@@ -129,194 +222,6 @@ impl Item {
         }
     }
 }
-fn walk(player: &mut Object, map: &Map, objects: &mut [Object], dx: i32, dy: i32) {
-    let x = player.x + dx;
-    let y = player.y + dy;
-
-    if x < 0 || x >= FIELD_WIDTH || y < 0 || y >= FIELD_HEIGHT || map.is_walkable(x, y) == false {
-        return;
-    }
-
-    if let Some(object) = objects.iter().find(|object| {
-        object.is_attackable() == false
-            && object.is_walkable() == false
-            && object.x == x
-            && object.y == y
-    }) {
-        log::log(
-            &format!(
-                "You cant pass {}{}",
-                object.description.chars().next().unwrap().to_lowercase(),
-                &object.description[1..]
-            ),
-            colors::DARKER_RED,
-        );
-        return;
-    }
-
-    player.x = x;
-    player.y = y;
-}
-
-fn get_object(x: i32, y: i32, objects: &mut [Object]) -> Option<&mut Object> {
-    objects
-        .iter_mut()
-        .find(|object| object.x == x && object.y == y)
-}
-
-fn attack(player: &mut Object, map: &Map, objects: &mut [Object], dx: i32, dy: i32) {
-    let x = player.x + dx;
-    let y = player.y + dy;
-
-    if map.is_walkable(x, y) == false {
-        log::log("There is no life in this wall", colors::LIGHT_BLUE);
-        return;
-    }
-    let object = get_object(x, y, objects);
-    if let Some(object) = object {
-        log::log(
-            &format!("{} life taken", object.description),
-            colors::LIGHT_BLUE,
-        );
-        log::log("Your mind cant stand this level of violence", colors::RED);
-        if object.kind == ObjectType::Chest {
-            log::log("PURE INNOCENT CHEST!11", colors::RED);
-            log::log("Humanity decreased for nothing", colors::RED);
-        } else {
-            log::log("Humanity decreased", colors::RED);
-            player.content.push(Item::Life {
-                kind: object.kind,
-                description: object.description.clone(),
-            });
-        }
-
-        player.humanity -= 1;
-
-        std::mem::replace(object, objects::garbage());
-        return;
-    }
-    log::log("You beat the air in panic", colors::LIGHT_RED);
-}
-
-fn interact(
-    player: &mut Object,
-    map: &mut Map,
-    objects: &mut [Object],
-    trade: &mut trade::Trade,
-    current_floor: &mut usize,
-    dx: i32,
-    dy: i32,
-) {
-    let x = player.x + dx;
-    let y = player.y + dy;
-
-    if let Some(object) = get_object(x, y, objects) {
-        match object.kind {
-            ObjectType::Chest if object.visited => {
-                log::log(
-                    "You desperately opens the same chest again",
-                    colors::LIGHTER_RED,
-                );
-                log::log("Still nothing", colors::LIGHTER_RED);
-            }
-            ObjectType::Chest => {
-                log::log("You open a chest and start looting", colors::GREEN);
-                if object.content.len() == 0 {
-                    log::log("Loot fairy says no", colors::RED);
-                } else {
-                    for loot in &object.content {
-                        log::log(
-                            &format!("you got {}", loot.description()),
-                            colors::DARKER_GREY,
-                        );
-                        player.content.push(loot.clone());
-                    }
-                }
-                object.visited = true;
-            }
-            ObjectType::Character => {
-                let index = objects
-                    .iter()
-                    .position(|object| object.x == x && object.y == y);
-                if let Some(index) = index {
-                    trade.open(index);
-                }
-            }
-            ObjectType::Door => {
-                drop(object);
-
-                let mut doors = vec![];
-                let mut visited = std::collections::HashSet::new();
-                visited.insert((x, y));
-                doors.push((x, y));
-                while doors.len() != 0 {
-                    let door = doors.pop().unwrap();
-                    let deltas = [
-                        (1, 0),
-                        (-1, 0),
-                        (0, 1),
-                        (0, -1),
-                        (1, 1),
-                        (1, -1),
-                        (-1, 1),
-                        (-1, -1),
-                    ];
-                    for (dx, dy) in &deltas {
-                        let pos = (door.0 + dx, door.1 + dy);
-                        if let Some(object) = get_object(pos.0, pos.1, objects) {
-                            if visited.contains(&pos) == false && object.kind == ObjectType::Door {
-                                visited.insert(pos);
-                                doors.push(pos);
-                            }
-                        }
-                    }
-                    if let Some(object) = get_object(door.0, door.1, objects) {
-                        object.opened ^= true;
-                        map.set(object.x, object.y, object.opened, object.opened);
-                    }
-                }
-            }
-            ObjectType::UpStair => {
-                if *current_floor < FLOORS - 1 {
-                    let gold = player.content.iter().fold(0, |sum, item| sum + item.gold());
-                    if gold < 7 {
-                        log::log("Your cost should be more than 7 gold", colors::DARK_RED);
-                        log::log(&format!("You cost {}", gold), colors::DARK_RED);
-                    } else {
-                        player.content.clear();
-                        *current_floor += 1;
-                        log::log("All your item sacrificied to the door", colors::LIGHTER_RED);
-                        log::log(
-                            "You ascended to the next level of the tower",
-                            colors::LIGHTER_RED,
-                        );
-                    }
-                } else {
-                    log::log(
-                        "That stair is broken and buried with rocks",
-                        colors::DARK_GREY,
-                    );
-                }
-            }
-            ObjectType::DownStair => {
-                if *current_floor > 0 {
-                    *current_floor -= 1;
-                    log::log("You escaped back in panic.", colors::DARK_RED);
-                } else {
-                    log::log(
-                        "That stair is broken and buried with rocks",
-                        colors::DARK_GREY,
-                    );
-                }
-            }
-            ObjectType::Garbage => {}
-        }
-    }
-}
-
-fn garbage_colect(objects: &mut Vec<Object>) {
-    objects.retain(|object| object.kind != ObjectType::Garbage);
-}
 
 pub fn panel<F: Fn(&mut OffscreenConsole, i32, i32)>(
     console: &mut RootConsole,
@@ -336,10 +241,12 @@ pub fn panel<F: Fn(&mut OffscreenConsole, i32, i32)>(
     console::blit(&offscreen, (0, 0), (width, height), console, (x, y), 1., 1.);
 }
 
-fn info_panel(player: &Object, console: &mut console::Root, floor: usize) {
+fn info_panel(state: &GameState, keymap: &Keymap, layout: Layout, console: &mut console::Root) {
+    let player = &state.player;
+    let floor = state.current_floor;
     panel(
         console,
-        FIELD_WIDTH,
+        layout.field_width,
         0,
         INFO_WIDTH,
         20,
@@ -392,6 +299,65 @@ fn info_panel(player: &Object, console: &mut console::Root, floor: usize) {
                 &format!("{}", floor + 1),
             );
             panel.set_default_foreground(colors::WHITE);
+            panel.print_ex(
+                width / 2,
+                5,
+                BackgroundFlag::Set,
+                TextAlignment::Right,
+                "Health:",
+            );
+            panel.set_default_foreground(colors::RED);
+            panel.print_ex(
+                width / 2 + 2,
+                5,
+                BackgroundFlag::Set,
+                TextAlignment::Left,
+                &format!("{}/{}", player.hp, player.max_hp),
+            );
+            panel.set_default_foreground(colors::WHITE);
+            panel.print_ex(
+                width / 2,
+                6,
+                BackgroundFlag::Set,
+                TextAlignment::Right,
+                "Turn:",
+            );
+            panel.print_ex(
+                width / 2 + 2,
+                6,
+                BackgroundFlag::Set,
+                TextAlignment::Left,
+                &format!("{}", state.log.turn()),
+            );
+            panel.print_ex(
+                width / 2,
+                7,
+                BackgroundFlag::Set,
+                TextAlignment::Right,
+                "Body:",
+            );
+            let abilities = player
+                .abilities
+                .iter()
+                .map(|ability| ability.name())
+                .collect::<Vec<_>>();
+            let body = if abilities.is_empty() {
+                "nothing special".to_string()
+            } else {
+                abilities.join(", ")
+            };
+            let body = if player.original {
+                body
+            } else {
+                format!("{}, not yours", body)
+            };
+            panel.print_ex(
+                width / 2 + 2,
+                7,
+                BackgroundFlag::Set,
+                TextAlignment::Left,
+                &body,
+            );
             for (n, _) in (0..player.humanity).enumerate() {
                 panel.put_char(
                     width / 2 + n as i32 * 2 + 2,
@@ -403,40 +369,59 @@ fn info_panel(player: &Object, console: &mut console::Root, floor: usize) {
         },
     );
 
+    let inventory = layout.inventory();
     panel(
         console,
-        FIELD_WIDTH,
-        20,
-        INFO_WIDTH,
-        20,
+        inventory.x,
+        inventory.y,
+        inventory.w,
+        inventory.h,
         Some("Inventory"),
         |panel, _width, _| {
-            let mut map = std::collections::BTreeMap::<String, i32>::new();
-            for item in &player.content {
-                *map.entry(format!("{}", item.description())).or_insert(0) += 1;
-            }
-            for (n, (item, amount)) in map.iter().enumerate() {
-                panel.print(1, n as i32 + 1, format!(" - {}, x{}", item, amount))
+            for (n, stack) in player.content.iter().enumerate() {
+                panel.print(
+                    1,
+                    INVENTORY_FIRST_ROW + n as i32,
+                    format!(" - {}, x{}", stack.item.description(), stack.count),
+                );
+                if state.selected_item == Some(n) {
+                    for x in 1..INFO_WIDTH - 1 {
+                        panel.set_char_background(
+                            x,
+                            INVENTORY_FIRST_ROW + n as i32,
+                            colors::DARKER_AMBER,
+                            BackgroundFlag::Set,
+                        );
+                    }
+                }
             }
         },
     );
 
     panel(
         console,
-        FIELD_WIDTH,
+        layout.field_width,
         40,
         INFO_WIDTH,
-        FIELD_HEIGHT - 40,
+        layout.field_height - 40,
         Some("Log"),
-        |panel, _width, _| {
-            for (n, (log, color)) in log::logs().iter().rev().take(38).enumerate() {
-                panel.set_default_foreground(*color);
+        |panel, _width, height| {
+            let rows = height - 2;
+            for (n, entry) in state
+                .log
+                .entries()
+                .iter()
+                .rev()
+                .take(rows as usize)
+                .enumerate()
+            {
+                panel.set_default_foreground(entry.color);
                 panel.print_ex(
                     1,
-                    38 - n as i32,
+                    rows - n as i32,
                     BackgroundFlag::Set,
                     TextAlignment::Left,
-                    log,
+                    &entry.text(),
                 );
                 panel.set_default_foreground(colors::WHITE);
             }
@@ -446,162 +431,286 @@ fn info_panel(player: &Object, console: &mut console::Root, floor: usize) {
     panel(
         console,
         0,
-        FIELD_HEIGHT,
-        FIELD_WIDTH + INFO_WIDTH,
+        layout.field_height,
+        layout.width(),
         HELP_HEIGHT,
         Some("Keybindings"),
-        |panel, _, _| {
-            panel.print(
-                1,
-                1,
-                &format!(
-                    "{}{}{}{} - walk",
-                    tcod::chars::ARROW_E,
-                    tcod::chars::ARROW_N,
-                    tcod::chars::ARROW_W,
-                    tcod::chars::ARROW_S
-                ),
-            );
-            panel.print(1, 2, "e - interact");
-            panel.print(25, 1, "a - violently take life");
-            panel.print(25, 2, "; - toggle observe mode");
+        |panel, _, height| {
+            let rows = height - 2;
+            let mut x = 1;
+            for column in keymap.help().chunks(rows as usize) {
+                for (n, line) in column.iter().enumerate() {
+                    panel.print(x, n as i32 + 1, line);
+                }
+                x += column
+                    .iter()
+                    .map(|line| line.chars().count())
+                    .max()
+                    .unwrap_or(0) as i32
+                    + 3;
+            }
         },
     );
 }
 
-struct Floor {
-    map: Map,
-    objects: Vec<Object>,
-    tile_map: map::TileMap,
+/// Value following `name` on the command line, like `--seed 42`.
+fn arg_value(name: &str) -> Option<String> {
+    let args = std::env::args().collect::<Vec<_>>();
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|n| args.get(n + 1))
+        .cloned()
 }
 
-fn make_floor(n: i32) -> Floor {
-    let mut objects = vec![];
-    let mut map = Map::new(FIELD_WIDTH, FIELD_HEIGHT);
-    let tile_map = make_map(&mut objects, FIELD_WIDTH as usize, FIELD_HEIGHT as usize, n);
-    for row in tile_map.iter() {
-        for tile_entity in row.iter() {
-            map.set(
-                tile_entity.x,
-                tile_entity.y,
-                tile_entity.transparent,
-                tile_entity.walkable,
-            )
-        }
+fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+fn fail(err: &str) -> ! {
+    eprintln!("{}", err);
+    std::process::exit(1);
+}
+
+/// World seed from `--seed <number>`, random if not given.
+fn parse_seed() -> u64 {
+    arg_number("--seed").unwrap_or_else(rand::random)
+}
+
+/// Number following `name` on the command line, exits when it is not a number.
+fn arg_number<T: std::str::FromStr>(name: &str) -> Option<T> {
+    arg_value(name).map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| fail(&format!("{} expects a number", name)))
+    })
+}
+
+/// settings.ron with everything the command line overrides.
+fn load_settings(data_dir: &str) -> Settings {
+    let mut settings = Settings::load(data_dir).unwrap_or_else(|err| fail(&err));
+    if let Some(width) = arg_number("--width") {
+        settings.width = width;
+    }
+    if let Some(height) = arg_number("--height") {
+        settings.height = height;
     }
+    if let Some(floors) = arg_number("--floors") {
+        settings.floors = floors;
+    }
+    if let Some(view_radius) = arg_number("--view-radius") {
+        settings.view_radius = view_radius;
+    }
+    if has_flag("--endless") {
+        settings.endless = true;
+    }
+    settings.validate().unwrap_or_else(|err| fail(&err));
+    settings
+}
 
-    for tile_row in tile_map.iter() {
-        for tile in tile_row.iter() {
-            if get_object(tile.x, tile.y, &mut objects).is_some() {
-                continue;
-            }
-            if map.is_walkable(tile.x, tile.y) && rand::random::<i32>() % 200 == 0 {
-                objects.push(Object {
-                    x: tile.x,
-                    y: tile.y,
-                    ..objects::chest()
-                });
-            }
-            if map.is_walkable(tile.x, tile.y) && rand::random::<i32>() % 310 == 0 {
-                objects.push(Object {
-                    x: tile.x,
-                    y: tile.y,
-                    ..objects::graybeard()
-                });
-            }
-            if map.is_walkable(tile.x, tile.y) && rand::random::<i32>() % 310 == 0 {
-                objects.push(Object {
-                    x: tile.x,
-                    y: tile.y,
-                    ..objects::frog()
-                });
-            }
+/// The screen part of settings.ron with the command line overrides.
+fn load_screen(data_dir: &str) -> Screen {
+    let mut screen = Screen::load(data_dir).unwrap_or_else(|err| fail(&err));
+    if let Some(field_width) = arg_number("--field-width") {
+        screen.field_width = field_width;
+    }
+    if let Some(field_height) = arg_number("--field-height") {
+        screen.field_height = field_height;
+    }
+    if let Some(camera_margin) = arg_number("--camera-margin") {
+        screen.camera_margin = camera_margin;
+    }
+    screen.validate().unwrap_or_else(|err| fail(&err));
+    screen
+}
+
+fn in_field(state: &GameState, (x, y): (i32, i32)) -> bool {
+    game::is_inside(&state.floor().map, x, y)
+}
+
+fn describe_cell(floor: &Floor, x: i32, y: i32) -> &str {
+    if let Some(object) = floor
+        .objects
+        .iter()
+        .find(|object| object.x == x && object.y == y)
+    {
+        &object.description
+    } else if !floor.map.is_in_fov(x, y) {
+        "You cant see clearly in the dark"
+    } else if !floor.map.is_walkable(x, y) {
+        "Blank wall"
+    } else {
+        "Nothing"
+    }
+}
+
+/// Map cell under the mouse, none when it points at a panel or past the edge of the floor.
+fn hovered_cell(state: &GameState, camera: &Camera, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+    camera.to_world(x, y).filter(|&cell| in_field(state, cell))
+}
+
+fn click(
+    state: &GameState,
+    root: &RootConsole,
+    layout: Layout,
+    camera: &Camera,
+    (x, y): (i32, i32),
+) -> Option<Command> {
+    if state.trade.is_opened() {
+        return state
+            .trade
+            .row_at(root, &state.player, x, y)
+            .map(Command::Select);
+    }
+    if let Some((_, row)) = layout.inventory().inside(x, y) {
+        let entry = row - INVENTORY_FIRST_ROW;
+        if entry >= 0 && (entry as usize) < state.player.content.len() {
+            return Some(Command::Select(entry as usize));
         }
+        return None;
     }
+    hovered_cell(state, camera, (x, y)).map(|(x, y)| Command::TravelTo(x, y))
+}
 
-    objects.push(Object {
-        x: FIELD_WIDTH / 2 - 1,
-        y: FIELD_HEIGHT / 2,
-        ..objects::upstairs()
-    });
-    objects.push(Object {
-        x: FIELD_WIDTH / 2 + 1,
-        y: FIELD_HEIGHT / 2,
-        ..objects::downstairs()
-    });
-    Floor {
-        map,
-        tile_map,
-        objects,
+/// Leaves the morgue file and the score behind a finished run.
+fn finish_run(state: &mut GameState, ending: Ending) -> (Vec<Score>, Option<usize>) {
+    let path = morgue::morgue_path(state);
+    match morgue::write_morgue(state, ending, &path) {
+        Ok(()) => state.log.add(
+            Category::System,
+            &format!("Morgue written to {}", path),
+            colors::GREEN,
+        ),
+        Err(err) => state.log.add(Category::System, &err, colors::RED),
     }
+
+    let score = Score {
+        score: morgue::score(state),
+        ending,
+        floor: state.current_floor,
+        turns: state.log.turn(),
+        seed: state.seed,
+        body: state.player.description.clone(),
+    };
+    morgue::record_score(morgue::SCORES_PATH, score).unwrap_or_else(|err| {
+        state.log.add(Category::System, &err, colors::RED);
+        (vec![], None)
+    })
 }
 
 fn main() {
-    let mut root = RootConsole::initializer()
-        .size(FIELD_WIDTH + INFO_WIDTH, FIELD_HEIGHT + HELP_HEIGHT)
-        .title("LifeTrader")
-        .init();
+    let data_dir = arg_value("--data").unwrap_or_else(|| data::DATA_DIR.to_string());
+    let data = Data::load(&data_dir).unwrap_or_else(|err| fail(&err));
+    let keymap = Keymap::load(&data_dir).unwrap_or_else(|err| fail(&err));
+    let settings = load_settings(&data_dir);
+    let screen = load_screen(&data_dir);
+
+    // --floor-stats <runs> builds the floors of that many seeds from --seed on and exits
+    if let Some(runs) = arg_value("--floor-stats") {
+        let runs = runs
+            .parse()
+            .unwrap_or_else(|_| fail("--floor-stats expects a number"));
+        let first_seed = arg_number("--seed").unwrap_or(0);
+        print!("{}", game::floor_stats(&data, &settings, first_seed, runs));
+        return;
+    }
 
-    let mut floors = vec![];
+    let playback =
+        arg_value("--replay").map(|path| replay::load(&path).unwrap_or_else(|err| fail(&err)));
+
+    if let Some(playback) = &playback {
+        if has_flag("--headless") {
+            let state = replay::play(playback, data);
+            if !playback.matches(&state) {
+                fail(&format!(
+                    "Replay desynced: final state hash {:016x}, recorded {:016x}",
+                    replay::state_hash(&state),
+                    playback.hash
+                ));
+            }
+            println!("Replay of {} commands matches", playback.commands.len());
+            return;
+        }
+    }
 
-    for n in 0..FLOORS {
-        floors.push(make_floor(n as i32 + 1));
+    let mut state = if let Some(playback) = &playback {
+        GameState::new(playback.seed, data, playback.settings)
+    } else if let Some(path) = arg_value("--load") {
+        save::load(&path, data).unwrap_or_else(|err| fail(&err))
+    } else {
+        GameState::new(parse_seed(), data, settings)
+    };
+    if let Some(path) = arg_value("--log-file") {
+        state.log.append_to(&path).unwrap_or_else(|err| fail(&err));
     }
 
-    let mut observe_x = 0;
-    let mut observe_y = 1;
+    let mut recording = arg_value("--record").map(|path| {
+        if playback.is_some() || arg_value("--load").is_some() {
+            fail("Only a new game can be recorded");
+        }
+        (path, replay::Replay::new(state.seed, state.settings))
+    });
+    let mut played = 0;
 
-    log::log("You entered the tower of darkness", colors::GREEN);
-    log::log("Your torch is going to fade out", colors::GREY);
-    log::log("And your mind as well", colors::DARKER_GREY);
-    log::log("You know exactly that your goal", colors::LIGHTER_GREY);
-    log::log("       is on the last floor", colors::LIGHTER_GREY);
+    let layout = Layout::new(&screen);
+    let mut camera = Camera::new(
+        layout.field_width,
+        layout.field_height,
+        screen.camera_margin,
+    );
+    let mut root = RootConsole::initializer()
+        .size(layout.width(), layout.height())
+        .title("LifeTrader")
+        .init();
 
-    // Set the map.
+    tcod::system::set_fps(FPS);
 
     let mut n = 0;
-
-    let mut player = Object {
-        x: 10,
-        y: 10,
-        ..objects::player()
-    };
-    let mut mode = Mode::Walk;
-    let mut trade = trade::Trade::default();
-    let mut current_floor = 0;
+    let mut hover = (-1, -1);
+    let mut history = History::default();
+    let mut scores: Option<(Vec<Score>, Option<usize>)> = None;
+    let mut showing_scores = false;
 
     while !root.window_closed() {
-        let floor = &mut floors[current_floor];
+        let floor = state.floor();
+        let player = &state.player;
 
         n += 1;
         root.clear();
 
-        floor.map.compute_fov(
-            player.x,
-            player.y,
-            VIEW_RADIUS as i32,
-            true,
-            FovAlgorithm::Basic,
-        );
+        // observe mode moves the view along with its cursor
+        let focus = if state.mode == Mode::Observe {
+            (state.observe_x, state.observe_y)
+        } else {
+            (player.x, player.y)
+        };
+        camera.follow(focus, floor.map.size());
 
         let noise = noise::Perlin::new();
 
         for tile_row in floor.tile_map.iter() {
             for tile in tile_row.iter() {
+                let (x, y) = match camera.to_screen(tile.x, tile.y) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
                 if floor.map.is_in_fov(tile.x, tile.y) {
-                    root.put_char(tile.x, tile.y, tile.ch, BackgroundFlag::Set);
+                    root.put_char(x, y, tile.ch, BackgroundFlag::Set);
                 } else {
-                    root.put_char_ex(tile.x, tile.y, tile.ch, GREY, BLACK);
+                    root.put_char_ex(x, y, tile.ch, GREY, BLACK);
                 }
             }
         }
 
         for tile_row in floor.tile_map.iter() {
             for tile in tile_row.iter() {
+                let (x, y) = match camera.to_screen(tile.x, tile.y) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
                 if floor.map.is_in_fov(tile.x, tile.y) {
                     let tx = tile.x - player.x;
                     let ty = tile.y - player.y;
-                    let r = ((tx * tx + ty * ty) as f64).sqrt() / VIEW_RADIUS;
+                    let r = ((tx * tx + ty * ty) as f64).sqrt() / state.settings.view_radius as f64;
                     let angle = (tx as f64 / ty as f64).atan();
 
                     let color = if noise.get([angle * 100., n as f64 / 20.]).abs() + 0.2 > r {
@@ -611,23 +720,21 @@ fn main() {
                         Color::new(150, 0, 0)
                     };
                     if tile.ch == BLOCK1 {
-                        root.put_char_ex(tile.x, tile.y, tile.ch, tile.color, Color::new(0, 0, 0));
+                        root.put_char_ex(x, y, tile.ch, tile.color, Color::new(0, 0, 0));
                     } else {
-                        root.put_char_ex(tile.x, tile.y, tile.ch, color, Color::new(0, 0, 0));
+                        root.put_char_ex(x, y, tile.ch, color, Color::new(0, 0, 0));
                     }
                 } else {
-                    root.put_char_ex(
-                        tile.x,
-                        tile.y,
-                        tile.ch,
-                        Color::new(55, 55, 55),
-                        Color::new(0, 0, 0),
-                    );
+                    root.put_char_ex(x, y, tile.ch, Color::new(55, 55, 55), Color::new(0, 0, 0));
                 }
             }
         }
 
         for object in floor.objects.iter() {
+            let (x, y) = match camera.to_screen(object.x, object.y) {
+                Some(cell) => cell,
+                None => continue,
+            };
             if floor.map.is_in_fov(object.x, object.y) {
                 let color = if object.kind == ObjectType::Chest && object.visited {
                     colors::GREY
@@ -641,126 +748,139 @@ fn main() {
                     object.ch
                 };
 
-                root.put_char_ex(object.x, object.y, ch, color, Color::new(0, 0, 0));
+                root.put_char_ex(x, y, ch, color, Color::new(0, 0, 0));
             }
         }
 
-        root.put_char(player.x, player.y, player.ch, BackgroundFlag::Set);
+        if let Some((x, y)) = camera.to_screen(player.x, player.y) {
+            root.put_char(x, y, player.ch, BackgroundFlag::Set);
+        }
 
-        if mode == Mode::Interact || mode == Mode::Attack {
-            root.print(0, FIELD_HEIGHT - 1, "Pick direction");
+        if state.mode == Mode::Interact || state.mode == Mode::Attack {
+            root.print(0, layout.field_height - 1, "Pick direction");
         }
 
-        if mode == Mode::Observe {
-            root.put_char_ex(
-                observe_x,
-                observe_y,
-                '.',
-                colors::WHITE,
-                colors::DARKER_BLUE,
+        if state.mode == Mode::Observe {
+            let (observe_x, observe_y) = (state.observe_x, state.observe_y);
+            if let Some((x, y)) = camera.to_screen(observe_x, observe_y) {
+                root.put_char_ex(x, y, '.', colors::WHITE, colors::DARKER_BLUE);
+            }
+            root.print(
+                0,
+                layout.field_height - 1,
+                describe_cell(floor, observe_x, observe_y),
             );
-            if let Some(object) = get_object(observe_x, observe_y, &mut floor.objects) {
-                root.print(0, FIELD_HEIGHT - 1, &object.description);
-            } else {
-                if floor.map.is_in_fov(observe_x, observe_y) == false {
-                    root.print(0, FIELD_HEIGHT - 1, "You cant see clearly in the dark");
-                } else if floor.map.is_walkable(observe_x, observe_y) == false {
-                    root.print(0, FIELD_HEIGHT - 1, "Blank wall");
-                } else {
-                    root.print(0, FIELD_HEIGHT - 1, "Nothing");
-                }
+        } else if state.mode == Mode::Walk && !state.trade.is_opened() {
+            if let Some((x, y)) = hovered_cell(&state, &camera, hover) {
+                root.print(0, layout.field_height - 1, describe_cell(floor, x, y));
+            }
+        }
+        info_panel(&state, &keymap, layout, &mut root);
+        state.trade.draw(&mut root, player, &keymap);
+        match (state.ending, &scores) {
+            (Some(_), Some((table, place))) if showing_scores => {
+                morgue::draw_scores(&mut root, table, *place)
             }
+            (Some(ending), _) => ending::draw_summary(&mut root, &state, ending),
+            (None, _) => {}
         }
-        info_panel(&player, &mut root, current_floor);
+        history.draw(&mut root, &state.log);
 
-        if trade.process(&mut root, &mut player, &mut floor.objects) == false {
-            root.flush();
-            let key = root.wait_for_keypress(true);
+        root.flush();
 
-            let mut direction = None;
-            match key {
-                Key { code: Up, .. } => {
-                    direction = Some((0, -1));
+        let command = match check_for_event(KEY_PRESS | MOUSE) {
+            Some((_, Event::Key(_))) if state.ending.is_some() && !history.is_opened() => {
+                if scores.is_none() || showing_scores {
+                    break;
                 }
-                Key { code: Down, .. } => {
-                    direction = Some((0, 1));
-                }
-                Key { code: Right, .. } => {
-                    direction = Some((1, 0));
-                }
-                Key { code: Left, .. } => {
-                    direction = Some((-1, 0));
-                }
-                Key { printable: 'e', .. } => {
-                    mode = Mode::Interact;
-                }
-                Key { printable: 'a', .. } => {
-                    mode = Mode::Attack;
-                }
-                Key { printable: ';', .. } if mode == Mode::Observe => {
-                    mode = Mode::Walk;
-                }
-                Key { printable: ';', .. } => {
-                    mode = Mode::Observe;
-                    observe_x = player.x;
-                    observe_y = player.y;
+                showing_scores = true;
+                None
+            }
+            Some((_, Event::Mouse(mouse))) if history.is_opened() => {
+                history.wheel(&root, &state.log, mouse);
+                None
+            }
+            Some((_, Event::Key(key))) if keymap.opens_history(key) => {
+                history.toggle();
+                None
+            }
+            Some((_, Event::Key(key))) if history.is_opened() => {
+                history.step(&root, &state.log, key, keymap.command(key));
+                None
+            }
+            Some((_, Event::Mouse(mouse))) => {
+                hover = (mouse.cx as i32, mouse.cy as i32);
+                // a finished run takes no more commands, only a key to move on
+                if mouse.lbutton_pressed && state.ending.is_none() {
+                    click(&state, &root, layout, &camera, hover)
+                } else {
+                    None
                 }
-
-                Key {
+            }
+            Some((
+                _,
+                Event::Key(Key {
                     code: Enter,
                     alt: true,
                     ..
-                } => {
-                    let fullscreen = root.is_fullscreen();
-                    root.set_fullscreen(!fullscreen);
-                }
-                _ => {
-                    mode = Mode::Walk;
+                }),
+            )) => {
+                let fullscreen = root.is_fullscreen();
+                root.set_fullscreen(!fullscreen);
+                None
+            }
+            Some((_, Event::Key(key))) if keymap.saves(key) => {
+                match save::save(&state, save::SAVE_PATH) {
+                    Ok(()) => state.log.add(
+                        Category::System,
+                        &format!("Game saved to {}", save::SAVE_PATH),
+                        colors::GREEN,
+                    ),
+                    Err(err) => state.log.add(Category::System, &err, colors::RED),
                 }
+                None
             }
-
-            if let Some((dx, dy)) = direction {
-                match mode {
-                    Mode::Walk => {
-                        walk(&mut player, &mut floor.map, &mut floor.objects, dx, dy);
-                        mode = Mode::Walk;
-                    }
-                    Mode::Attack => {
-                        attack(&mut player, &mut floor.map, &mut floor.objects, dx, dy);
-                        mode = Mode::Walk;
-                    }
-                    Mode::Interact => {
-                        interact(
-                            &mut player,
-                            &mut floor.map,
-                            &mut floor.objects,
-                            &mut trade,
-                            &mut current_floor,
-                            dx,
-                            dy,
-                        );
-                        mode = Mode::Walk;
-                    }
-                    Mode::Observe => {
-                        observe_x += dx;
-                        if observe_x < 0 {
-                            observe_x = 0;
-                        }
-                        if observe_x >= FIELD_WIDTH {
-                            observe_x = FIELD_WIDTH - 1;
-                        }
-                        observe_y += dy;
-                        if observe_y < 0 {
-                            observe_y = 0;
-                        }
-                        if observe_y >= FIELD_HEIGHT {
-                            observe_y = FIELD_HEIGHT - 1;
+            Some((_, Event::Key(key))) => keymap.command(key),
+            _ => None,
+        };
+
+        if let Some(command) = command {
+            if let Some(playback) = &playback {
+                if let Some(command) = playback.commands.get(played) {
+                    state.step(*command);
+                    played += 1;
+                    if played == playback.commands.len() {
+                        if playback.matches(&state) {
+                            state.log.add(
+                                Category::System,
+                                "Replay finished, no desync",
+                                colors::GREEN,
+                            );
+                        } else {
+                            state.log.add(
+                                Category::System,
+                                "Replay desynced from the recording",
+                                colors::RED,
+                            );
                         }
                     }
                 }
+            } else {
+                if let Some((_, replay)) = &mut recording {
+                    replay.record(command);
+                }
+                state.step(command);
+                if let (Some(ending), None) = (state.ending, &scores) {
+                    scores = Some(finish_run(&mut state, ending));
+                }
             }
         }
+    }
 
-        garbage_colect(&mut floor.objects);
+    if let Some((path, mut replay)) = recording {
+        replay.finish(&state);
+        if let Err(err) = replay::save(&replay, &path) {
+            eprintln!("{}", err);
+        }
     }
 }
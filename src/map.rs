@@ -1,84 +1,182 @@
-use crate::*;
-use rand::Rng;
-
-use std::f32::consts::PI;
-use tcod::chars::{BLOCK1, DCROSS, DHLINE, DNE, DNW, DSE, DSW, DTEEE, DTEEN, DTEES, DTEEW, DVLINE};
+use crate::{
+    bsp::Bsp,
+    caves::Caves,
+    data::Data,
+    prefab::Prefabs,
+    reach::{self, Repairs},
+    tower::Tower,
+    *,
+};
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+use serde_derive::Deserialize;
+
+use tcod::chars::{DCROSS, DHLINE, DNE, DNW, DSE, DSW, DTEEE, DTEEN, DTEES, DTEEW, DVLINE};
 
 pub type TileMap = Vec<Vec<Tile>>;
 
-const DOOR_CH: char = '+';
+pub const DOOR_CH: char = '+';
+/// Floors made again when a part of them stays cut off, before giving up and walling it up.
+const REGENERATIONS: i32 = 5;
+
+/// Where the stairs of a floor stand, both on walkable tiles. The up stair
+/// leads to the down stair of the floor above.
+#[derive(Debug, Clone, Copy)]
+pub struct Stairs {
+    pub up: (i32, i32),
+    pub down: (i32, i32),
+}
+
+/// Lays out the walls and floors of one floor and picks the places for its stairs.
+/// Doors are `DOOR_CH` tiles, they become door objects once the layout is done.
+pub trait FloorGenerator {
+    fn generate(&self, map: &mut TileMap, floor_number: i32, rng: &mut Pcg32) -> Stairs;
+}
+
+/// Which generator a floor is built with, picked per floor in objects.ron.
+#[derive(Deserialize, Debug, Default, Clone, PartialEq)]
+pub enum Generator {
+    #[default]
+    Tower,
+    /// Rooms in the leaves of a binary space partition, joined by corridors.
+    Bsp { min_room: i32, max_room: i32 },
+    /// Cellular automaton, `fill` percent of rock smoothed over `steps` passes.
+    Caves { fill: i32, steps: i32 },
+    /// Like `Bsp`, but the rooms are copied from prefabs.ron.
+    Prefab { min_room: i32, max_room: i32 },
+}
+
+impl Generator {
+    pub fn build<'a>(&self, data: &'a Data) -> Box<dyn FloorGenerator + 'a> {
+        match *self {
+            Generator::Tower => Box::new(Tower),
+            Generator::Bsp { min_room, max_room } => Box::new(Bsp { min_room, max_room }),
+            Generator::Caves { fill, steps } => Box::new(Caves { fill, steps }),
+            Generator::Prefab { min_room, max_room } => Box::new(Prefabs {
+                min_room,
+                max_room,
+                prefabs: &data.prefabs.prefabs,
+            }),
+        }
+    }
+}
 
+/// Builds the floor until everything on it can be reached from the stairs,
+/// after `REGENERATIONS` tries whatever is still cut off gets walled up.
 pub fn make_map(
     objects: &mut Vec<Object>,
     map_width: usize,
     map_height: usize,
     floor_number: i32,
-) -> TileMap {
-    let mut map = vec![vec![Tile::wall(); map_height]; map_width];
-
-    //    let rooms: Vec<Rect> = vec![];
-
-    fill_empty(floor_number, &mut map);
-    draw_circle(floor_number, &mut map);
-
-    make_rooms(floor_number, &mut map);
-    //    for ( x, mut map_row) in map.iter_mut().enumerate() {
-    //        for (y, mut map_tile) in map_row.iter_mut().enumerate() {
-    //            let in_circle = (x as i32 - center)*(x as i32 - center) + (y as i32 - center) * (y as i32 - center) - (radius * radius) as i32;
-    //            if in_circle >= 0 && in_circle <= 100{
-    //                *map_tile = Tile {
-    //                    x: x as i32,
-    //                    y: y as i32,
-    //                    ch: '#',
-    //                    walkable: false,
-    //                    description: String::from("Tower wall"),
-    //                    transparent: false
-    //                }
-    //
-    //            } else {
-    //                *map_tile = Tile {
-    //                    x: x as i32,
-    //                    y: y as i32,
-    //                    ch: '.',
-    //                    walkable: true,
-    //                    description: String::from("Tower floor"),
-    //                    transparent: true
-    //                }
-    //            }
-    //        }
-    //    }
-
-    //    make_rooms(&mut map);
+    data: &Data,
+    rng: &mut Pcg32,
+) -> (TileMap, Stairs, Repairs) {
+    let generator = data.objects.floor(floor_number).generator.build(data);
+    let mut repairs = Repairs::default();
+    let (mut map, stairs) = loop {
+        let mut map = walls(map_width, map_height);
+        let stairs = generator.generate(&mut map, floor_number, rng);
+        let force = repairs.regenerated >= REGENERATIONS;
+        if force {
+            for &(x, y) in &[stairs.down, stairs.up] {
+                map[x as usize][y as usize] = Tile::floor(x, y);
+            }
+            carve_corridor(&mut map, stairs.down, stairs.up);
+        }
+        if reach::repair(&mut map, stairs.down, rng, &mut repairs, force) {
+            let (up_x, up_y) = stairs.up;
+            if reach::reachable(&map, stairs.down)[up_x as usize][up_y as usize] {
+                break (map, stairs);
+            }
+        }
+        repairs.regenerated += 1;
+    };
 
-    //    fill_random(&mut map);
     smooth_walls(&mut map);
-    fill_objects(&mut map, objects);
+    fill_objects(&mut map, objects, floor_number, data, rng);
     for object in objects {
         if object.kind == ObjectType::Door {
-            map[object.y as usize][object.x as usize].walkable = false;
-            map[object.y as usize][object.x as usize].transparent = false;
+            map[object.x as usize][object.y as usize].walkable = false;
+            map[object.x as usize][object.y as usize].transparent = false;
+        }
+    }
+    (map, stairs, repairs)
+}
+
+fn walls(map_width: usize, map_height: usize) -> TileMap {
+    (0..map_width)
+        .map(|x| {
+            (0..map_height)
+                .map(|y| Tile {
+                    x: x as i32,
+                    y: y as i32,
+                    ..Tile::wall()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+pub fn carve_room(map: &mut TileMap, room: Rect) {
+    for x in room.x..room.x + room.w {
+        for y in room.y..room.y + room.h {
+            map[x as usize][y as usize] = Tile::floor(x, y);
+        }
+    }
+}
+
+/// Digs an L shaped corridor between two points, horizontal leg first.
+pub fn carve_corridor(map: &mut TileMap, from: (i32, i32), to: (i32, i32)) {
+    let (x1, y1) = from;
+    let (x2, y2) = to;
+    for x in x1.min(x2)..=x1.max(x2) {
+        if !map[x as usize][y1 as usize].walkable {
+            map[x as usize][y1 as usize] = Tile::floor(x, y1);
         }
     }
-    map
+    for y in y1.min(y2)..=y1.max(y2) {
+        if !map[x2 as usize][y as usize].walkable {
+            map[x2 as usize][y as usize] = Tile::floor(x2, y);
+        }
+    }
+}
+
+/// A random walkable tile at least a quarter of the map away from `from`,
+/// any walkable one when none is that far.
+pub fn far_floor(map: &TileMap, from: (i32, i32), rng: &mut Pcg32) -> (i32, i32) {
+    let far = (map.len().min(map[0].len()) / 4) as i32;
+    let floors = map
+        .iter()
+        .flatten()
+        .filter(|tile| tile.walkable && tile.ch != DOOR_CH && (tile.x, tile.y) != from)
+        .map(|tile| (tile.x, tile.y))
+        .collect::<Vec<_>>();
+    let far_floors = floors
+        .iter()
+        .cloned()
+        .filter(|(x, y)| (x - from.0).pow(2) + (y - from.1).pow(2) >= far.pow(2))
+        .collect::<Vec<_>>();
+    *far_floors
+        .choose(rng)
+        .or_else(|| floors.choose(rng))
+        .unwrap_or(&from)
+}
+
+pub fn door(x: i32, y: i32) -> Tile {
+    Tile::new(x, y, DOOR_CH, WHITE, "Door", true, false)
 }
 
-fn fill_objects(map: &mut TileMap, objects: &mut Vec<Object>) {
+fn fill_objects(
+    map: &mut TileMap,
+    objects: &mut Vec<Object>,
+    floor_number: i32,
+    data: &Data,
+    rng: &mut Pcg32,
+) {
     for (_, map_row) in map.iter_mut().enumerate() {
         for (_, map_tile) in map_row.iter_mut().enumerate() {
             if map_tile.ch == DOOR_CH {
-                objects.push(Object {
-                    x: map_tile.x,
-                    y: map_tile.y,
-                    ch: DOOR_CH,
-                    color: WHITE,
-                    description: String::from("Closed door"),
-                    humanity: 3,
-                    visited: false,
-                    kind: ObjectType::Door,
-                    content: Vec::new(),
-                    opened: false,
-                    life_equivalent: 3,
-                })
+                objects.push(data.spawn("door", map_tile.x, map_tile.y, floor_number, rng))
             }
         }
     }
@@ -193,183 +291,3 @@ fn smooth_walls(map: &mut TileMap) {
         }
     }
 }
-
-fn draw_circle(floor_number: i32, map: &mut TileMap) {
-    let center = (FIELD_WIDTH / 2) as f32;
-    let radius = ((FIELD_WIDTH as f32 / (1. + floor_number as f32 * 0.10)) / 2.0) as f32;
-
-    let mut t = 0.;
-    let step = 0.0001;
-    let mut door_tick = 0.;
-    while t <= PI * 2. {
-        use rand::Rng;
-
-        let x = center + radius * t.cos();
-        let y = center + radius * t.sin();
-
-        let door_chance: i32 = rand::thread_rng().gen_range(0, 9000);
-        if door_chance > 8997 {
-            door_tick = 0.;
-        }
-
-        if door_tick >= 2000. {
-            map[x as usize][y as usize] = Tile {
-                x: x as i32,
-                y: y as i32,
-                ch: '#',
-                color: WHITE,
-                walkable: false,
-                description: String::from("Tower wall"),
-                transparent: false,
-            };
-        } else {
-            map[x as usize][y as usize] = Tile {
-                x: x as i32,
-                y: y as i32,
-                ch: DOOR_CH,
-                color: WHITE,
-                walkable: true,
-                description: String::from("Door"),
-                transparent: false,
-            };
-
-            door_tick += 1.;
-        }
-
-        t += step;
-    }
-}
-
-fn make_rooms(floor_number: i32, map: &mut TileMap) {
-    let radius = ((FIELD_WIDTH as f32 / (1. + floor_number as f32 * 0.10)) / 2.0) as f32;
-    let center = (FIELD_WIDTH / 2) as f32;
-
-    draw_circle(floor_number + 5, map);
-    draw_circle(floor_number + 18, map);
-
-    let random_angle = rand::thread_rng().gen_range(PI * 0.2, PI * 0.4);
-    let mut current_ray_angle = random_angle;
-
-    while current_ray_angle <= PI * 2. {
-        let mut t: f32 = 0.;
-        let step = 0.1;
-
-        let mut hit_first_wall = false;
-        while t <= radius {
-            let x = center + t * current_ray_angle.cos();
-            let y = center + t * current_ray_angle.sin();
-
-            if !map[x as usize][y as usize].walkable {
-                hit_first_wall = true;
-            }
-
-            if hit_first_wall {
-                map[x as usize][y as usize] = Tile {
-                    x: x as i32,
-                    y: y as i32,
-                    ch: '#',
-                    color: WHITE,
-                    walkable: false,
-                    description: String::from("Tower wall"),
-                    transparent: false,
-                };
-            }
-            t += step;
-        }
-
-        current_ray_angle += random_angle;
-    }
-    //    let mut t :f32 = 0.;
-    //    let step= 0.01;
-    //
-    //    while t < PI * 2 {
-    //        let x = center + radius * t.cos();
-    //        let y = center + radius * t.sin();
-    //
-    //        t += step;
-    //    }
-    //    for _ in 0..10 {
-    //        let height: i32 = rand::thread_rng().gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE);
-    //        let width: i32 = rand::thread_rng().gen_range(MIN_ROOM_SIZE, MAX_ROOM_SIZE);
-    //
-    //        let x: i32 = rand::thread_rng().gen_range(0, FIELD_WIDTH as i32 - width);
-    //        let y: i32 = rand::thread_rng().gen_range(0, FIELD_HEIGHT as i32 - height);
-    //
-    //        let room = Rect::new(x, y, width, height);
-    //
-    //        create_room(room, map);
-    //    }
-}
-
-//fn create_room(room: Rect, map: &mut TileMap) {
-//    print!("{:?}", room);
-//
-//    for x in (room.x + 1)..room.x + room.w {
-//        for y in (room.y + 1)..room.y + room.h {
-//            map[x as usize][y as usize] = Tile::empty(x, y);
-//        }
-//    }
-//}
-
-fn fill_empty(floor_number: i32, map: &mut TileMap) {
-    let center = (FIELD_WIDTH / 2) as f32;
-    let radius = ((FIELD_WIDTH as f32 / (1. + floor_number as f32 * 0.10)) / 2.0) as f32;
-
-    if floor_number == 1 {
-        for (x, map_row) in map.iter_mut().enumerate() {
-            for (y, map_tile) in map_row.iter_mut().enumerate() {
-                if ((x as f32 - center).powi(2) as f32 + (y as f32 - center).powi(2) as f32).sqrt()
-                    < radius
-                {
-                    *map_tile = Tile {
-                        x: x as i32,
-                        y: y as i32,
-                        walkable: true,
-                        color: WHITE,
-                        transparent: true,
-                        description: String::from("Floor, you can step on it"),
-                        ch: '.',
-                    };
-                } else {
-                    *map_tile = Tile {
-                        x: x as i32,
-                        y: y as i32,
-                        walkable: true,
-                        color: Color::new(10, 80, 10),
-                        transparent: true,
-                        description: String::from("Grass, it is green"),
-                        ch: BLOCK1,
-                    };
-                }
-            }
-        }
-    } else {
-        for (x, map_row) in map.iter_mut().enumerate() {
-            for (y, map_tile) in map_row.iter_mut().enumerate() {
-                if ((x as f32 - center).powi(2) as f32 + (y as f32 - center).powi(2) as f32).sqrt()
-                    < radius
-                {
-                    *map_tile = Tile {
-                        x: x as i32,
-                        y: y as i32,
-                        walkable: true,
-                        color: WHITE,
-                        transparent: true,
-                        description: String::from("Floor, you can step on it"),
-                        ch: '.',
-                    };
-                } else {
-                    *map_tile = Tile {
-                        x: x as i32,
-                        y: y as i32,
-                        walkable: false,
-                        color: WHITE,
-                        transparent: true,
-                        description: String::from(""),
-                        ch: ' ',
-                    };
-                }
-            }
-        }
-    }
-}
@@ -0,0 +1,151 @@
+use serde_derive::{Deserialize, Serialize};
+use std::fmt::Write;
+use tcod::{colors, console::Console, BackgroundFlag, RootConsole, TextAlignment};
+
+use crate::{ending::Ending, game::GameState, panel};
+
+pub const SCORES_PATH: &str = "ld44.scores";
+const KEPT_SCORES: usize = 10;
+const MORGUE_LOGS: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    pub score: i32,
+    pub ending: Ending,
+    pub floor: usize,
+    pub turns: u32,
+    pub seed: u64,
+    pub body: String,
+}
+
+/// Climbing counts most, then what is left of the player, reaching the goal tops it off.
+pub fn score(state: &GameState) -> i32 {
+    let goal = if state.stats.reached_goal { 1000 } else { 0 };
+    (state.current_floor as i32 + 1) * 100
+        + state.player.humanity.max(0) * 25
+        + state.player.content.gold() * 5
+        + state.stats.trades * 10
+        + goal
+}
+
+pub fn morgue_path(state: &GameState) -> String {
+    format!("morgue-{}-{}.txt", state.seed, state.log.turn())
+}
+
+pub fn write_morgue(state: &GameState, ending: Ending, path: &str) -> Result<(), String> {
+    let player = &state.player;
+    let mut text = String::new();
+    // writing into a String can not fail
+    let _ = writeln!(text, "LifeTrader morgue");
+    let _ = writeln!(text);
+    let _ = writeln!(text, "{}: {}", ending.title(), ending.text());
+    let _ = writeln!(text, "Score: {}", score(state));
+    let _ = writeln!(text, "Seed: {}", state.seed);
+    let _ = writeln!(text, "Turns: {}", state.log.turn());
+    let _ = writeln!(text, "Floor reached: {}", state.current_floor + 1);
+    let own = if player.original {
+        ""
+    } else {
+        ", not your own"
+    };
+    let _ = writeln!(text, "Body: {}{}", player.description, own);
+    let _ = writeln!(text, "Health: {}/{}", player.hp, player.max_hp);
+    let _ = writeln!(text, "Humanity: {}", player.humanity);
+    let _ = writeln!(text, "Lives taken: {}", state.stats.lives_taken);
+    let _ = writeln!(text, "Bodies swapped: {}", state.stats.body_swaps);
+    let _ = writeln!(text, "Trades signed: {}", state.stats.trades);
+    let _ = writeln!(text);
+    let _ = writeln!(text, "Inventory, {} gold worth:", player.content.gold());
+    for stack in player.content.iter() {
+        let _ = writeln!(text, " - {}, x{}", stack.item.description(), stack.count);
+    }
+    let _ = writeln!(text);
+    let _ = writeln!(text, "Last messages:");
+    let entries = state.log.entries();
+    for entry in &entries[entries.len().saturating_sub(MORGUE_LOGS)..] {
+        let _ = writeln!(text, "{:>5} {}", entry.turn, entry.text());
+    }
+
+    std::fs::write(path, text).map_err(|err| format!("Cant write {}: {}", path, err))
+}
+
+/// The best runs so far, a missing file is just an empty table.
+pub fn load_scores(path: &str) -> Result<Vec<Score>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => ron::de::from_str(&text).map_err(|err| format!("Cant parse {}: {}", path, err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(format!("Cant read {}: {}", path, err)),
+    }
+}
+
+/// Adds the run to the table, returns the table and the place the run took if it made it there.
+pub fn record_score(path: &str, score: Score) -> Result<(Vec<Score>, Option<usize>), String> {
+    let mut scores = load_scores(path)?;
+    let place = scores
+        .iter()
+        .position(|other| other.score < score.score)
+        .unwrap_or(scores.len());
+    scores.insert(place, score);
+    scores.truncate(KEPT_SCORES);
+
+    let text = ron::ser::to_string_pretty(&scores, ron::ser::PrettyConfig::default())
+        .map_err(|err| format!("Cant write {}: {}", path, err))?;
+    std::fs::write(path, text).map_err(|err| format!("Cant write {}: {}", path, err))?;
+
+    let place = if place < scores.len() {
+        Some(place)
+    } else {
+        None
+    };
+    Ok((scores, place))
+}
+
+pub fn draw_scores(console: &mut RootConsole, scores: &[Score], place: Option<usize>) {
+    let (width, height) = (90, KEPT_SCORES as i32 + 8);
+    let x = (console.width() - width) / 2;
+    let y = (console.height() - height) / 2;
+    panel(
+        console,
+        x,
+        y,
+        width,
+        height,
+        Some("Best runs"),
+        |panel, width, height| {
+            panel.print(
+                2,
+                2,
+                format!(
+                    "{:>3} {:>6} {:>5} {:>6}  {:<33} {}",
+                    "", "score", "floor", "turns", "ending", "body"
+                ),
+            );
+            for (n, score) in scores.iter().enumerate() {
+                if place == Some(n) {
+                    panel.set_default_foreground(colors::GREEN);
+                }
+                panel.print(
+                    2,
+                    n as i32 + 4,
+                    format!(
+                        "{:>3} {:>6} {:>5} {:>6}  {:<33} {}",
+                        n + 1,
+                        score.score,
+                        score.floor + 1,
+                        score.turns,
+                        score.ending.title(),
+                        score.body
+                    ),
+                );
+                panel.set_default_foreground(colors::WHITE);
+            }
+            panel.print_ex(
+                width / 2,
+                height - 2,
+                BackgroundFlag::Set,
+                TextAlignment::Center,
+                "Press any key to leave the tower",
+            );
+        },
+    );
+}
@@ -1,7 +1,8 @@
-use crate::Item;
+use crate::{ai::Behavior, body::Ability, inventory::Inventory};
+use serde_derive::{Deserialize, Serialize};
 use tcod::{colors, Color};
 
-#[derive(PartialEq, Debug, Clone, Copy, Hash, Eq)]
+#[derive(PartialEq, Debug, Clone, Copy, Hash, Eq, Serialize, Deserialize)]
 pub enum ObjectType {
     Chest,
     Character,
@@ -9,8 +10,10 @@ pub enum ObjectType {
     Door,
     UpStair,
     DownStair,
+    Goal,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Object {
     pub x: i32,
     pub y: i32,
@@ -19,10 +22,24 @@ pub struct Object {
     pub description: String,
     pub color: Color,
     pub kind: ObjectType,
-    pub content: Vec<Item>,
+    pub content: Inventory,
     pub visited: bool,
+    pub seen: bool,
     pub opened: bool,
     pub life_equivalent: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub power: i32,
+    pub defense: i32,
+    pub behavior: Behavior,
+    pub speed: i32,
+    pub energy: i32,
+    pub home: (i32, i32),
+    pub abilities: Vec<Ability>,
+    /// Wears the body the player started the run in.
+    pub original: bool,
+    /// Turns left following the player, 0 for anyone but summoned allies.
+    pub ally_turns: i32,
 }
 
 impl Object {
@@ -34,142 +51,18 @@ impl Object {
             ObjectType::Door => self.opened,
             ObjectType::UpStair => false,
             ObjectType::DownStair => false,
+            ObjectType::Goal => false,
         }
     }
 
     pub fn is_attackable(&self) -> bool {
         match self.kind {
+            ObjectType::Chest | ObjectType::Character => self.hp > 0,
             _ => false,
         }
     }
 }
 
-fn random_subset(names: &[&str]) -> Vec<crate::Item> {
-    let amount = rand::random::<u32>() % 5;
-    (0..amount)
-        .map(|_| crate::Item::Thing {
-            description: names[rand::random::<usize>() % names.len()].into(),
-            gold: (rand::random::<i32>() % 3).abs(),
-        })
-        .collect::<Vec<_>>()
-}
-pub fn player() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: '@',
-        humanity: 5,
-        description: "The human player".into(),
-        kind: ObjectType::Character,
-        color: colors::WHITE,
-        visited: false,
-        content: vec![],
-        opened: false,
-        life_equivalent: 10,
-    }
-}
-
-pub fn chest() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: '=',
-        humanity: 2,
-        description: "The old chest".into(),
-        kind: ObjectType::Chest,
-        color: colors::DARK_BLUE,
-        visited: false,
-        content: random_subset(&[
-            "cursed diary",
-            "dried finger",
-            "silver coin",
-            "weird box",
-            "prism stone",
-            "broken bone",
-            "binocularus",
-            "uglified skull",
-        ]),
-        opened: false,
-        life_equivalent: 2,
-    }
-}
-
-pub fn graybeard() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: 't',
-        humanity: 10,
-        description: "Graybeard trader".into(),
-        kind: ObjectType::Character,
-        color: colors::WHITE,
-        visited: false,
-        content: random_subset(&[
-            "cursed book",
-            "witch cloak",
-            "guardian armor",
-            "steel statuette",
-            "ripped pants",
-            "torn shirt",
-            "fig leaf",
-            "bunch of nails",
-            "unidentified poison",
-            "ancient key",
-            "yellow key",
-        ]),
-        opened: false,
-        life_equivalent: (rand::random::<i32>() % 5).abs() + 5,
-    }
-}
-
-pub fn frog() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: '^',
-        humanity: 2,
-        description: "The weird frog".into(),
-        kind: ObjectType::Character,
-        color: colors::GREEN,
-        visited: false,
-        content: random_subset(&["green foot", "green tail", "gren eyeball"]),
-        opened: false,
-        life_equivalent: (rand::random::<i32>() % 3).abs() + 1,
-    }
-}
-
-pub fn upstairs() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: '>',
-        humanity: 2,
-        description: "Staircase up".into(),
-        kind: ObjectType::UpStair,
-        color: colors::GREEN,
-        visited: false,
-        content: vec![],
-        opened: false,
-        life_equivalent: 100,
-    }
-}
-
-pub fn downstairs() -> Object {
-    Object {
-        x: 0,
-        y: 0,
-        ch: '<',
-        humanity: 2,
-        description: "Staircase down".into(),
-        kind: ObjectType::DownStair,
-        color: colors::DARK_GREEN,
-        visited: false,
-        content: vec![],
-        opened: false,
-        life_equivalent: 100,
-    }
-}
-
 pub fn garbage() -> Object {
     Object {
         x: 0,
@@ -180,8 +73,20 @@ pub fn garbage() -> Object {
         kind: ObjectType::Garbage,
         color: colors::WHITE,
         visited: false,
-        content: vec![],
+        seen: false,
+        content: Inventory::default(),
         opened: false,
         life_equivalent: 0,
+        hp: 0,
+        max_hp: 0,
+        power: 0,
+        defense: 0,
+        behavior: Behavior::Stay,
+        speed: 0,
+        energy: 0,
+        home: (0, 0),
+        abilities: vec![],
+        original: false,
+        ally_turns: 0,
     }
 }
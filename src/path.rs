@@ -0,0 +1,34 @@
+use tcod::{pathfinding::AStar, Map};
+
+use crate::{game::is_blocked, objects::Object};
+
+/// `from` itself is left out. A blocked target is approached as close as possible.
+pub fn find(
+    map: &Map,
+    objects: &[Object],
+    from: (i32, i32),
+    to: (i32, i32),
+) -> Option<Vec<(i32, i32)>> {
+    let (width, height) = map.size();
+    let mut path = AStar::new_from_callback(
+        width,
+        height,
+        |_, (x, y)| {
+            if (x, y) == to || !is_blocked(x, y, map, objects) {
+                1.0
+            } else {
+                0.0
+            }
+        },
+        1.41,
+    );
+    if !path.find(from, to) {
+        return None;
+    }
+
+    let mut way = path.walk().collect::<Vec<_>>();
+    if is_blocked(to.0, to.1, map, objects) && way.last() == Some(&to) {
+        way.pop();
+    }
+    Some(way)
+}
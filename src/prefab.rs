@@ -0,0 +1,118 @@
+use rand::{seq::SliceRandom, Rng};
+use rand_pcg::Pcg32;
+use serde_derive::Deserialize;
+
+use crate::{
+    bsp,
+    map::{self, FloorGenerator, Stairs, TileMap, DOOR_CH},
+    Rect, Tile,
+};
+
+/// A hand drawn room: `#` wall, `.` floor, `+` door, a space leaves the rock alone.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Prefab {
+    pub name: String,
+    pub rows: Vec<String>,
+}
+
+impl Prefab {
+    pub fn width(&self) -> i32 {
+        self.rows[0].chars().count() as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.rows.len() as i32
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.rows.is_empty() {
+            return Err(format!("Prefab {} has no rows", self.name));
+        }
+        for row in &self.rows {
+            if row.chars().count() as i32 != self.width() {
+                return Err(format!("Prefab {} has rows of different width", self.name));
+            }
+            if let Some(ch) = row.chars().find(|ch| !"#.+ ".contains(*ch)) {
+                return Err(format!("Prefab {} has unknown tile {:?}", self.name, ch));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct PrefabTable {
+    pub prefabs: Vec<Prefab>,
+}
+
+impl PrefabTable {
+    pub fn validate(&self) -> Result<(), String> {
+        for prefab in &self.prefabs {
+            prefab.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// Bsp layout with a random fitting prefab in every leaf, a plain room where none fits.
+pub struct Prefabs<'a> {
+    pub min_room: i32,
+    pub max_room: i32,
+    pub prefabs: &'a [Prefab],
+}
+
+impl<'a> FloorGenerator for Prefabs<'a> {
+    fn generate(&self, map: &mut TileMap, _floor_number: i32, rng: &mut Pcg32) -> Stairs {
+        let mut rooms = vec![];
+        for leaf in bsp::leaves(map, self.min_room, self.max_room, rng) {
+            let fitting = self
+                .prefabs
+                .iter()
+                .filter(|prefab| prefab.width() <= leaf.w - 2 && prefab.height() <= leaf.h - 2)
+                .collect::<Vec<_>>();
+            let room = match fitting.choose(rng) {
+                Some(prefab) => {
+                    let x = leaf.x + 1 + rng.gen_range(0, leaf.w - 2 - prefab.width() + 1);
+                    let y = leaf.y + 1 + rng.gen_range(0, leaf.h - 2 - prefab.height() + 1);
+                    stamp(map, prefab, x, y, rng.gen(), rng.gen())
+                }
+                None => {
+                    let room = bsp::room_in(leaf, self.min_room, self.max_room, rng);
+                    map::carve_room(map, room);
+                    room
+                }
+            };
+            rooms.push(room);
+        }
+        bsp::connect(map, &rooms, rng)
+    }
+}
+
+/// Copies the prefab with its top left corner at `x`, `y`, mirrored if asked.
+fn stamp(map: &mut TileMap, prefab: &Prefab, x: i32, y: i32, flip_x: bool, flip_y: bool) -> Rect {
+    let (width, height) = (prefab.width(), prefab.height());
+    for (row_n, row) in prefab.rows.iter().enumerate() {
+        for (column_n, ch) in row.chars().enumerate() {
+            let (mut dx, mut dy) = (column_n as i32, row_n as i32);
+            if flip_x {
+                dx = width - 1 - dx;
+            }
+            if flip_y {
+                dy = height - 1 - dy;
+            }
+            let (tile_x, tile_y) = (x + dx, y + dy);
+            let tile = match ch {
+                '.' => Tile::floor(tile_x, tile_y),
+                DOOR_CH => map::door(tile_x, tile_y),
+                '#' => Tile {
+                    x: tile_x,
+                    y: tile_y,
+                    ..Tile::wall()
+                },
+                _ => continue,
+            };
+            map[tile_x as usize][tile_y as usize] = tile;
+        }
+    }
+    Rect::new(x, y, width, height)
+}
@@ -0,0 +1,238 @@
+use rand::seq::SliceRandom;
+use rand_pcg::Pcg32;
+
+use crate::{
+    map::{self, TileMap, DOOR_CH},
+    Tile,
+};
+
+/// Unreachable regions this small are filled with wall instead of failing the floor.
+const POCKET: usize = 30;
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Repairs {
+    pub doors: i32,
+    /// Regions filled with wall, pockets or whatever was left after the last attempt.
+    pub sealed: i32,
+    pub regenerated: i32,
+}
+
+impl Repairs {
+    pub fn needed(&self) -> bool {
+        self.doors > 0 || self.sealed > 0 || self.regenerated > 0
+    }
+}
+
+/// Doors block the way only until someone opens them.
+fn passable(tile: &Tile) -> bool {
+    tile.walkable || tile.ch == DOOR_CH
+}
+
+/// Neighbours along diagonals count as connected.
+fn label(map: &TileMap) -> (Vec<Vec<Option<usize>>>, Vec<usize>) {
+    let (width, height) = (map.len() as i32, map[0].len() as i32);
+    let mut labels = vec![vec![None; height as usize]; width as usize];
+    let mut sizes = vec![];
+    for x in 0..width {
+        for y in 0..height {
+            if !passable(&map[x as usize][y as usize]) || labels[x as usize][y as usize].is_some() {
+                continue;
+            }
+            let region = sizes.len();
+            let mut size = 0;
+            let mut stack = vec![(x, y)];
+            labels[x as usize][y as usize] = Some(region);
+            while let Some((x, y)) = stack.pop() {
+                size += 1;
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        let (x, y) = (x + dx, y + dy);
+                        if x < 0 || y < 0 || x >= width || y >= height {
+                            continue;
+                        }
+                        if passable(&map[x as usize][y as usize])
+                            && labels[x as usize][y as usize].is_none()
+                        {
+                            labels[x as usize][y as usize] = Some(region);
+                            stack.push((x, y));
+                        }
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+    }
+    (labels, sizes)
+}
+
+/// Connects everything to the region around `anchor`: single walls between them
+/// get doors, small pockets left over get walled up. False when a bigger region
+/// stays cut off and the floor should be made again, unless `force` seals it too.
+pub fn repair(
+    map: &mut TileMap,
+    anchor: (i32, i32),
+    rng: &mut Pcg32,
+    repairs: &mut Repairs,
+    force: bool,
+) -> bool {
+    let (width, height) = (map.len(), map[0].len());
+    loop {
+        let (labels, sizes) = label(map);
+        let main = match labels[anchor.0 as usize][anchor.1 as usize] {
+            Some(main) => main,
+            None => return false,
+        };
+        if sizes.len() == 1 {
+            return true;
+        }
+
+        // walls with the main region on one side and a cut off one on the other
+        let mut candidates = vec![vec![]; sizes.len()];
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                if map[x][y].walkable {
+                    continue;
+                }
+                for &(a, b) in &[
+                    (labels[x - 1][y], labels[x + 1][y]),
+                    (labels[x][y - 1], labels[x][y + 1]),
+                ] {
+                    match (a, b) {
+                        (Some(a), Some(b)) if a == main && b != main => candidates[b].push((x, y)),
+                        (Some(a), Some(b)) if b == main && a != main => candidates[a].push((x, y)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut carved = false;
+        for walls in candidates.iter() {
+            if let Some(&(x, y)) = walls.choose(rng) {
+                map[x][y] = map::door(x as i32, y as i32);
+                repairs.doors += 1;
+                carved = true;
+            }
+        }
+        if carved {
+            continue;
+        }
+
+        let cut_off = (0..sizes.len()).filter(|&region| region != main);
+        if !force && cut_off.clone().any(|region| sizes[region] > POCKET) {
+            return false;
+        }
+        for region in cut_off {
+            seal(map, &labels, region);
+            repairs.sealed += 1;
+        }
+        return true;
+    }
+}
+
+pub fn reachable(map: &TileMap, from: (i32, i32)) -> Vec<Vec<bool>> {
+    let (labels, _) = label(map);
+    let main = labels[from.0 as usize][from.1 as usize];
+    labels
+        .iter()
+        .map(|column| {
+            column
+                .iter()
+                .map(|label| label.is_some() && *label == main)
+                .collect()
+        })
+        .collect()
+}
+
+fn seal(map: &mut TileMap, labels: &[Vec<Option<usize>>], region: usize) {
+    for (x, column) in labels.iter().enumerate() {
+        for (y, label) in column.iter().enumerate() {
+            if *label == Some(region) {
+                map[x][y] = Tile {
+                    x: x as i32,
+                    y: y as i32,
+                    ..Tile::wall()
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Rect;
+    use rand::SeedableRng;
+
+    fn rock(width: usize, height: usize) -> TileMap {
+        (0..width)
+            .map(|x| {
+                (0..height)
+                    .map(|y| Tile {
+                        x: x as i32,
+                        y: y as i32,
+                        ..Tile::wall()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn all_reachable(map: &TileMap, from: (i32, i32)) -> bool {
+        let reachable = reachable(map, from);
+        map.iter()
+            .flatten()
+            .all(|tile| !passable(tile) || reachable[tile.x as usize][tile.y as usize])
+    }
+
+    #[test]
+    fn single_wall_gets_a_door() {
+        let mut map = rock(20, 10);
+        map::carve_room(&mut map, Rect::new(1, 1, 8, 8));
+        map::carve_room(&mut map, Rect::new(10, 1, 8, 8));
+        let mut repairs = Repairs::default();
+        let rng = &mut Pcg32::seed_from_u64(1);
+        assert!(repair(&mut map, (2, 2), rng, &mut repairs, false));
+        assert_eq!(repairs.doors, 1);
+        assert_eq!(repairs.sealed, 0);
+        assert_eq!(map[9].iter().filter(|tile| tile.ch == DOOR_CH).count(), 1);
+        assert!(all_reachable(&map, (2, 2)));
+    }
+
+    #[test]
+    fn small_pocket_is_sealed() {
+        let mut map = rock(30, 10);
+        map::carve_room(&mut map, Rect::new(1, 1, 8, 8));
+        map::carve_room(&mut map, Rect::new(20, 2, 3, 3));
+        let mut repairs = Repairs::default();
+        let rng = &mut Pcg32::seed_from_u64(1);
+        assert!(repair(&mut map, (2, 2), rng, &mut repairs, false));
+        assert_eq!(repairs.doors, 0);
+        assert_eq!(repairs.sealed, 1);
+        assert!(!map[21][3].walkable);
+        assert!(all_reachable(&map, (2, 2)));
+    }
+
+    #[test]
+    fn big_region_needs_force() {
+        let mut map = rock(30, 10);
+        map::carve_room(&mut map, Rect::new(1, 1, 8, 8));
+        map::carve_room(&mut map, Rect::new(15, 1, 8, 8));
+        let mut repairs = Repairs::default();
+        let rng = &mut Pcg32::seed_from_u64(1);
+        assert!(!repair(&mut map, (2, 2), rng, &mut repairs, false));
+        assert!(repair(&mut map, (2, 2), rng, &mut repairs, true));
+        assert_eq!(repairs.sealed, 1);
+        assert!(!map[16][2].walkable);
+        assert!(all_reachable(&map, (2, 2)));
+    }
+
+    #[test]
+    fn anchor_in_rock_fails() {
+        let mut map = rock(20, 10);
+        map::carve_room(&mut map, Rect::new(1, 1, 8, 8));
+        let mut repairs = Repairs::default();
+        let rng = &mut Pcg32::seed_from_u64(1);
+        assert!(!repair(&mut map, (12, 5), rng, &mut repairs, true));
+    }
+}
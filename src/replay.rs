@@ -0,0 +1,180 @@
+use serde_derive::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+use crate::{
+    data::Data,
+    game::{Command, GameState},
+    settings::Settings,
+};
+
+const REPLAY_MAGIC: &[u8; 4] = b"LD4R";
+/// Bump when `Command` or the game rules change after a release in a way that makes
+/// old replays play differently.
+const REPLAY_VERSION: u32 = 1;
+
+/// Everything needed to play a run again: the world seed and settings, every command
+/// in the order it was issued and the hash of the state it ended in.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    pub seed: u64,
+    pub settings: Settings,
+    pub commands: Vec<Command>,
+    pub hash: u64,
+}
+
+impl Replay {
+    pub fn new(seed: u64, settings: Settings) -> Replay {
+        Replay {
+            seed,
+            settings,
+            commands: vec![],
+            hash: 0,
+        }
+    }
+
+    pub fn record(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    pub fn finish(&mut self, state: &GameState) {
+        self.hash = state_hash(state);
+    }
+
+    pub fn matches(&self, state: &GameState) -> bool {
+        self.hash == state_hash(state)
+    }
+}
+
+/// FNV-1a over the serialized state, so the hash does not depend on
+/// the std hasher of whatever compiler built the game.
+pub fn state_hash(state: &GameState) -> u64 {
+    let bytes = bincode::serialize(state).expect("Game state is always serializable");
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// Runs the whole replay without a window and returns the state it ended in.
+pub fn play(replay: &Replay, data: Data) -> GameState {
+    let mut state = GameState::new(replay.seed, data, replay.settings);
+    for command in &replay.commands {
+        state.step(*command);
+    }
+    state
+}
+
+pub fn save(replay: &Replay, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("Cant create {}: {}", path, err))?;
+    let mut writer = BufWriter::new(file);
+
+    writer
+        .write_all(REPLAY_MAGIC)
+        .map_err(|err| format!("Cant write {}: {}", path, err))?;
+    bincode::serialize_into(&mut writer, &REPLAY_VERSION)
+        .and_then(|_| bincode::serialize_into(&mut writer, replay))
+        .map_err(|err| format!("Cant write {}: {}", path, err))
+}
+
+pub fn load(path: &str) -> Result<Replay, String> {
+    let file = File::open(path).map_err(|err| format!("Cant open {}: {}", path, err))?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|err| format!("Cant read {}: {}", path, err))?;
+    if &magic != REPLAY_MAGIC {
+        return Err(format!("{} is not a LifeTrader replay", path));
+    }
+
+    let version: u32 = bincode::deserialize_from(&mut reader)
+        .map_err(|err| format!("Cant read {}: {}", path, err))?;
+    if version != REPLAY_VERSION {
+        return Err(format!(
+            "{} was recorded by version {} of the game, only version {} can be played",
+            path, version, REPLAY_VERSION
+        ));
+    }
+
+    bincode::deserialize_from(&mut reader).map_err(|err| format!("{} is corrupted: {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{data, objects::ObjectType};
+    use rand::{seq::SliceRandom, SeedableRng};
+    use rand_pcg::Pcg32;
+
+    /// A long run of everything a player can do, picked at random but always the same.
+    fn commands() -> Vec<Command> {
+        let choices = [
+            Command::Direction(0, -1),
+            Command::Direction(0, 1),
+            Command::Direction(-1, 0),
+            Command::Direction(1, 0),
+            Command::Direction(-1, -1),
+            Command::Direction(1, 1),
+            Command::Interact,
+            Command::Attack,
+            Command::Observe,
+            Command::Confirm,
+            Command::Cancel,
+            Command::Use,
+            Command::Select(0),
+            Command::Travel(ObjectType::UpStair),
+            Command::Travel(ObjectType::Chest),
+            Command::TravelTo(40, 40),
+        ];
+        let mut rng = Pcg32::seed_from_u64(3);
+        (0..400)
+            .map(|_| *choices.choose(&mut rng).unwrap())
+            .collect()
+    }
+
+    fn record(seed: u64, data: &Data) -> (Replay, GameState) {
+        let settings = Settings::default();
+        let mut state = GameState::new(seed, data.clone(), settings);
+        let mut replay = Replay::new(seed, settings);
+        for command in commands() {
+            replay.record(command);
+            state.step(command);
+        }
+        replay.finish(&state);
+        (replay, state)
+    }
+
+    #[test]
+    fn replay_ends_in_the_recorded_state() {
+        let data = Data::load(data::DATA_DIR).unwrap();
+        let (replay, state) = record(7, &data);
+        assert!(state.log.turn() > 0);
+        let replayed = play(&replay, data);
+        assert!(replay.matches(&replayed));
+        assert_eq!(state_hash(&replayed), state_hash(&state));
+    }
+
+    #[test]
+    fn replay_survives_the_file() {
+        let data = Data::load(data::DATA_DIR).unwrap();
+        let (replay, _) = record(11, &data);
+        let path = std::env::temp_dir().join("ld44-replay-test.rpl");
+        let path = path.to_str().unwrap();
+        save(&replay, path).unwrap();
+        let loaded = load(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded.commands, replay.commands);
+        assert!(loaded.matches(&play(&loaded, data)));
+    }
+
+    #[test]
+    fn another_seed_ends_elsewhere() {
+        let data = Data::load(data::DATA_DIR).unwrap();
+        let (first, _) = record(7, &data);
+        let (second, _) = record(8, &data);
+        assert_ne!(first.hash, second.hash);
+    }
+}
@@ -0,0 +1,56 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+use crate::{data::Data, game::GameState};
+
+pub const SAVE_PATH: &str = "ld44.sav";
+
+const SAVE_MAGIC: &[u8; 4] = b"LD44";
+/// Bump when anything saved with the game changes its layout after a release.
+const SAVE_VERSION: u32 = 1;
+
+pub fn save(state: &GameState, path: &str) -> Result<(), String> {
+    let file = File::create(path).map_err(|err| format!("Cant create {}: {}", path, err))?;
+    let mut writer = BufWriter::new(file);
+
+    writer
+        .write_all(SAVE_MAGIC)
+        .map_err(|err| format!("Cant write {}: {}", path, err))?;
+    bincode::serialize_into(&mut writer, &SAVE_VERSION)
+        .and_then(|_| bincode::serialize_into(&mut writer, state))
+        .and_then(|_| bincode::serialize_into(&mut writer, &state.log))
+        .map_err(|err| format!("Cant write {}: {}", path, err))
+}
+
+/// Data files are not part of the save, the game continues with the ones given.
+pub fn load(path: &str, data: Data) -> Result<GameState, String> {
+    let file = File::open(path).map_err(|err| format!("Cant open {}: {}", path, err))?;
+    let mut reader = BufReader::new(file);
+
+    let mut magic = [0; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|err| format!("Cant read {}: {}", path, err))?;
+    if &magic != SAVE_MAGIC {
+        return Err(format!("{} is not a LifeTrader save", path));
+    }
+
+    let version: u32 = bincode::deserialize_from(&mut reader)
+        .map_err(|err| format!("Cant read {}: {}", path, err))?;
+    if version != SAVE_VERSION {
+        return Err(format!(
+            "{} was saved by version {} of the game, only version {} can be loaded",
+            path, version, SAVE_VERSION
+        ));
+    }
+
+    let mut state: GameState = bincode::deserialize_from(&mut reader)
+        .map_err(|err| format!("{} is corrupted: {}", path, err))?;
+    state.data = data;
+    state.log = bincode::deserialize_from(&mut reader)
+        .map_err(|err| format!("{} is corrupted: {}", path, err))?;
+
+    Ok(state)
+}
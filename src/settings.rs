@@ -0,0 +1,99 @@
+use serde_derive::{Deserialize, Serialize};
+
+use crate::data;
+
+/// Smallest map the generators still make sense on.
+const MIN_SIZE: i32 = 20;
+
+/// Read from settings.ron, the command line can override every value.
+/// Saved with the game, a run keeps its shape no matter what the file says later.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub width: i32,
+    pub height: i32,
+    pub floors: usize,
+    pub view_radius: i32,
+    /// No heart of the tower, the floors keep going up and get harder.
+    pub endless: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            width: 80,
+            height: 80,
+            floors: 3,
+            view_radius: 20,
+            endless: false,
+        }
+    }
+}
+
+/// How much of the floor the window shows, read from the same settings.ron.
+/// Not saved with the game, a loaded run is shown the way the file says now.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Screen {
+    pub field_width: i32,
+    pub field_height: i32,
+    /// Tiles kept between the player and the edge of the map panel before it scrolls.
+    pub camera_margin: i32,
+}
+
+impl Default for Screen {
+    fn default() -> Screen {
+        Screen {
+            field_width: 80,
+            field_height: 50,
+            camera_margin: 10,
+        }
+    }
+}
+
+impl Settings {
+    /// Whether the heart of the tower waits on this floor, counted from 1.
+    pub fn is_top(&self, floor: i32) -> bool {
+        !self.endless && floor as usize == self.floors
+    }
+
+    /// settings.ron from the data directory, defaults when there is none.
+    pub fn load(dir: &str) -> Result<Settings, String> {
+        if !std::path::Path::new(&format!("{}/settings.ron", dir)).exists() {
+            return Ok(Settings::default());
+        }
+        data::load_file(dir, "settings.ron")
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.width < MIN_SIZE || self.height < MIN_SIZE {
+            return Err(format!(
+                "Map should be at least {}x{}, not {}x{}",
+                MIN_SIZE, MIN_SIZE, self.width, self.height
+            ));
+        }
+        if self.floors == 0 {
+            return Err("The tower needs at least one floor".to_string());
+        }
+        if self.view_radius <= 0 {
+            return Err("View radius should be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl Screen {
+    pub fn load(dir: &str) -> Result<Screen, String> {
+        if !std::path::Path::new(&format!("{}/settings.ron", dir)).exists() {
+            return Ok(Screen::default());
+        }
+        data::load_file(dir, "settings.ron")
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.camera_margin < 0 {
+            return Err("Camera margin cant be negative".to_string());
+        }
+        Ok(())
+    }
+}
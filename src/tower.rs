@@ -0,0 +1,179 @@
+use rand::Rng;
+use rand_pcg::Pcg32;
+use std::f32::consts::PI;
+use tcod::{chars::BLOCK1, colors::WHITE, Color};
+
+use crate::{
+    map::{self, FloorGenerator, Stairs, TileMap, DOOR_CH},
+    Tile,
+};
+
+/// The round tower the game started with: a ring of wall with doors in it,
+/// two more rings inside and walls cast from the middle like rays.
+/// The way up is in the very middle, the way down somewhere far from it.
+pub struct Tower;
+
+impl FloorGenerator for Tower {
+    fn generate(&self, map: &mut TileMap, floor_number: i32, rng: &mut Pcg32) -> Stairs {
+        fill_empty(floor_number, map);
+        draw_circle(floor_number, map, rng);
+        make_rooms(floor_number, map, rng);
+
+        let up = (map.len() as i32 / 2, map[0].len() as i32 / 2);
+        Stairs {
+            up,
+            down: map::far_floor(map, up, rng),
+        }
+    }
+}
+
+/// Middle of the map and the radius of the tower on the floor, higher floors are narrower
+/// down to a third of the map.
+fn shape(map: &TileMap, floor_number: i32) -> ((f32, f32), f32) {
+    let (width, height) = (map.len() as f32, map[0].len() as f32);
+    let widest = width.min(height) / 2.0;
+    // an endless tower would narrow down to nothing
+    let radius = (widest / (1. + floor_number as f32 * 0.10)).max(widest / 3.);
+    ((width / 2., height / 2.), radius)
+}
+
+fn draw_circle(floor_number: i32, map: &mut TileMap, rng: &mut Pcg32) {
+    let ((center_x, center_y), radius) = shape(map, floor_number);
+
+    let mut t = 0.;
+    let step = 0.0001;
+    let mut door_tick = 0.;
+    while t <= PI * 2. {
+        let x = center_x + radius * t.cos();
+        let y = center_y + radius * t.sin();
+
+        let door_chance: i32 = rng.gen_range(0, 9000);
+        if door_chance > 8997 {
+            door_tick = 0.;
+        }
+
+        if door_tick >= 2000. {
+            map[x as usize][y as usize] = Tile {
+                x: x as i32,
+                y: y as i32,
+                ch: '#',
+                color: WHITE,
+                walkable: false,
+                description: String::from("Tower wall"),
+                transparent: false,
+            };
+        } else {
+            map[x as usize][y as usize] = Tile {
+                x: x as i32,
+                y: y as i32,
+                ch: DOOR_CH,
+                color: WHITE,
+                walkable: true,
+                description: String::from("Door"),
+                transparent: false,
+            };
+
+            door_tick += 1.;
+        }
+
+        t += step;
+    }
+}
+
+fn make_rooms(floor_number: i32, map: &mut TileMap, rng: &mut Pcg32) {
+    let ((center_x, center_y), radius) = shape(map, floor_number);
+
+    draw_circle(floor_number + 5, map, rng);
+    draw_circle(floor_number + 18, map, rng);
+
+    let random_angle = rng.gen_range(PI * 0.2, PI * 0.4);
+    let mut current_ray_angle = random_angle;
+
+    while current_ray_angle <= PI * 2. {
+        let mut t: f32 = 0.;
+        let step = 0.1;
+
+        let mut hit_first_wall = false;
+        while t <= radius {
+            let x = center_x + t * current_ray_angle.cos();
+            let y = center_y + t * current_ray_angle.sin();
+
+            if !map[x as usize][y as usize].walkable {
+                hit_first_wall = true;
+            }
+
+            if hit_first_wall {
+                map[x as usize][y as usize] = Tile {
+                    x: x as i32,
+                    y: y as i32,
+                    ch: '#',
+                    color: WHITE,
+                    walkable: false,
+                    description: String::from("Tower wall"),
+                    transparent: false,
+                };
+            }
+            t += step;
+        }
+
+        current_ray_angle += random_angle;
+    }
+}
+
+fn fill_empty(floor_number: i32, map: &mut TileMap) {
+    let ((center_x, center_y), radius) = shape(map, floor_number);
+
+    if floor_number == 1 {
+        for (x, map_row) in map.iter_mut().enumerate() {
+            for (y, map_tile) in map_row.iter_mut().enumerate() {
+                if ((x as f32 - center_x).powi(2) + (y as f32 - center_y).powi(2)).sqrt() < radius {
+                    *map_tile = Tile {
+                        x: x as i32,
+                        y: y as i32,
+                        walkable: true,
+                        color: WHITE,
+                        transparent: true,
+                        description: String::from("Floor, you can step on it"),
+                        ch: '.',
+                    };
+                } else {
+                    *map_tile = Tile {
+                        x: x as i32,
+                        y: y as i32,
+                        walkable: true,
+                        color: Color::new(10, 80, 10),
+                        transparent: true,
+                        description: String::from("Grass, it is green"),
+                        ch: BLOCK1,
+                    };
+                }
+            }
+        }
+    } else {
+        for (x, map_row) in map.iter_mut().enumerate() {
+            for (y, map_tile) in map_row.iter_mut().enumerate() {
+                if ((x as f32 - center_x).powi(2) + (y as f32 - center_y).powi(2)).sqrt() < radius {
+                    *map_tile = Tile {
+                        x: x as i32,
+                        y: y as i32,
+                        walkable: true,
+                        color: WHITE,
+                        transparent: true,
+                        description: String::from("Floor, you can step on it"),
+                        ch: '.',
+                    };
+                } else {
+                    *map_tile = Tile {
+                        x: x as i32,
+                        y: y as i32,
+                        walkable: false,
+                        color: WHITE,
+                        transparent: true,
+                        description: String::from(""),
+                        ch: ' ',
+                    };
+                }
+            }
+        }
+    }
+}
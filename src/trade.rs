@@ -1,33 +1,99 @@
-use std::collections::HashMap;
-use tcod::{
-    console::Console,
-    input::{Key, KeyCode::*},
-    *,
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tcod::{console::Console, *};
+
+use crate::{
+    body,
+    ending::Stats,
+    game::Command,
+    humanity,
+    inventory::{Inventory, Stack},
+    keymap::Keymap,
+    log::{Category, Log},
+    objects::Object,
+    panel, Item, Rect,
 };
 
-use crate::{objects::Object, panel, Item};
+/// Rows of "I will take from you", the player's stacks follow the first two.
+const LIFE_ROW: i32 = 0;
+const HUMANITY_ROW: i32 = 1;
+const STACK_ROWS: i32 = 2;
+/// Panel row the first of them is printed on.
+const FIRST_ROW: i32 = 2;
+
+fn stack_index(row: i32) -> usize {
+    (row - STACK_ROWS) as usize
+}
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Deservables {
+    gold: i32,
     life: bool,
-    items: Vec<Item>,
+    items: Inventory,
+    /// What is offered beyond the deserved life and goods.
+    gold_left: i32,
+}
+
+/// What a trader asks for a piece of everything he owns, the most expensive goods first.
+/// Humane traders charge a callous player more.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PriceTable {
+    life: i32,
+    life_for_yours: i32,
+    items: Vec<(Stack, i32)>,
+}
+
+impl PriceTable {
+    fn new(trader: &Object, player: &Object) -> PriceTable {
+        let mut items = trader
+            .content
+            .iter()
+            .map(|stack| {
+                let price = humanity::markup(trader, player, stack.item.gold() + 1);
+                let price = body::haggled(player, price);
+                (stack.clone(), price)
+            })
+            .collect::<Vec<_>>();
+        items.sort_by(|(stack, price), (other_stack, other_price)| {
+            other_price.cmp(price).then_with(|| {
+                stack
+                    .item
+                    .description()
+                    .cmp(&other_stack.item.description())
+            })
+        });
+
+        PriceTable {
+            life: body::haggled(
+                player,
+                humanity::markup(trader, player, trader.life_equivalent + 2),
+            ),
+            life_for_yours: body::haggled(
+                player,
+                humanity::markup(trader, player, (trader.life_equivalent + 1) / 2),
+            ),
+            items,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Trade {
     opened: bool,
     magic_index: usize,
     selection: usize,
-    selected: HashMap<i32, i32>,
+    selected: BTreeMap<i32, i32>,
+    prices: PriceTable,
     deservables: Deservables,
 }
 
 impl Trade {
-    pub fn open(&mut self, index: usize) {
+    pub fn open(&mut self, index: usize, trader: &Object, player: &Object) {
         self.opened = true;
         self.magic_index = index;
         self.selection = 0;
         self.selected.clear();
+        self.prices = PriceTable::new(trader, player);
         self.deservables = Deservables::default();
     }
 
@@ -39,42 +105,83 @@ impl Trade {
         }
     }
 
-    fn update_deservables(&mut self, player: &Object, objects: &[Object]) {
+    fn update_deservables(&mut self, player: &Object) {
         self.deservables = Deservables::default();
 
-        let mut gold = self.selected.iter().fold(0, |sum, (key, value)| {
-            if *key != 0 {
-                player.content[*key as usize - 1].gold() * value + sum
-            } else {
-                sum + value * 3
-            }
-        });
-        if (*self.selected.get(&0).unwrap_or(&0) == 1
-            && gold * 2 >= objects[self.magic_index].life_equivalent)
-            || gold >= objects[self.magic_index].life_equivalent + 2
-        {
-            self.deservables.life = true;
+        let gold = self
+            .selected
+            .iter()
+            .fold(0, |sum, (key, value)| match *key {
+                LIFE_ROW => sum + value * 3,
+                HUMANITY_ROW => sum + value * humanity::HUMANITY_GOLD,
+                _ => player.content.get(stack_index(*key)).unwrap().item.gold() * value + sum,
+            });
+        self.deservables.gold = gold;
+        let life_price = if *self.selected.get(&LIFE_ROW).unwrap_or(&0) == 1 {
+            self.prices.life_for_yours
         } else {
-            self.deservables.life = false;
+            self.prices.life
+        };
+        self.deservables.life = gold >= life_price;
+
+        let mut gold_left = if self.deservables.life {
+            gold - life_price
+        } else {
+            gold
+        };
+        for (stack, price) in &self.prices.items {
+            let count = stack.count.min(gold_left / price);
+            self.deservables.items.add(stack.item.clone(), count);
+            gold_left -= count * price;
         }
+        self.deservables.gold_left = gold_left;
+    }
 
-        let object = &objects[self.magic_index];
-        while gold > 0 && object.content.len() != 0 {
-            let item = object.content[rand::random::<usize>() % object.content.len()].clone();
-            assert!(item.gold() >= 0);
-            self.deservables.items.push(item.clone());
-            gold -= item.gold() + 1;
+    fn selectable(&self, player: &Object, selection: usize) -> i32 {
+        match selection as i32 {
+            LIFE_ROW if humanity::can_offer_life(player) => 1,
+            HUMANITY_ROW if humanity::can_sell_humanity(player) => player.humanity,
+            LIFE_ROW | HUMANITY_ROW => 0,
+            row => player
+                .content
+                .get(stack_index(row))
+                .map_or(0, |stack| stack.count),
         }
     }
 
-    pub fn process(
-        &mut self,
-        console: &mut RootConsole,
-        player: &mut Object,
-        objects: &mut Vec<Object>,
-    ) -> bool {
+    fn last_row(player: &Object) -> usize {
+        player.content.len() + STACK_ROWS as usize - 1
+    }
+
+    fn offer_panel(console: &RootConsole) -> Rect {
+        Rect::new(
+            12,
+            15,
+            (console.width() - 20) / 2 - 1,
+            console.height() - 31,
+        )
+    }
+
+    pub fn row_at(&self, console: &RootConsole, player: &Object, x: i32, y: i32) -> Option<usize> {
+        if !self.opened {
+            return None;
+        }
+        let (_, line) = Trade::offer_panel(console).inside(x, y)?;
+        let row = line - FIRST_ROW;
+        if row >= 0 && row as usize <= Trade::last_row(player) {
+            Some(row as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_opened(&self) -> bool {
+        self.opened
+    }
+
+    pub fn draw(&self, console: &mut RootConsole, player: &Object, keymap: &Keymap) {
         if self.opened == false {
-            return false;
+            return;
         }
 
         panel(
@@ -95,36 +202,66 @@ impl Trade {
             },
         );
 
+        let offer = Trade::offer_panel(console);
         panel(
             console,
-            12,
-            15,
-            (console.width() - 20) / 2 - 1,
-            console.height() - 27,
+            offer.x,
+            offer.y,
+            offer.w,
+            offer.h,
             Some("I will take from you"),
             |panel, _, _| {
-                panel.print(
-                    1,
-                    2,
-                    format!(
-                        " - Your own life {}/{}",
-                        self.selected.get(&0).unwrap_or(&0),
-                        1
-                    ),
-                );
-                self.set_background(panel, 2, 0);
-                for (n, item) in player.content.iter().enumerate() {
+                if humanity::can_offer_life(player) {
                     panel.print(
                         1,
-                        n as i32 + 3,
+                        FIRST_ROW + LIFE_ROW,
                         format!(
-                            " - {} {}/{}",
-                            item.description(),
-                            self.selected.get(&((n + 1) as i32)).unwrap_or(&0),
+                            " - Your own life {}/{}",
+                            self.selected.get(&LIFE_ROW).unwrap_or(&0),
                             1
                         ),
                     );
-                    self.set_background(panel, n as i32 + 3, n + 1);
+                } else {
+                    panel.print(
+                        1,
+                        FIRST_ROW + LIFE_ROW,
+                        " - Your own life, worth nothing to me",
+                    );
+                }
+                self.set_background(panel, FIRST_ROW + LIFE_ROW, LIFE_ROW as usize);
+                if humanity::can_sell_humanity(player) {
+                    panel.print(
+                        1,
+                        FIRST_ROW + HUMANITY_ROW,
+                        format!(
+                            " - Your humanity ({}g) {}/{}",
+                            humanity::HUMANITY_GOLD,
+                            self.selected.get(&HUMANITY_ROW).unwrap_or(&0),
+                            player.humanity
+                        ),
+                    );
+                } else {
+                    panel.print(
+                        1,
+                        FIRST_ROW + HUMANITY_ROW,
+                        " - Your humanity, not for sale yet",
+                    );
+                }
+                self.set_background(panel, FIRST_ROW + HUMANITY_ROW, HUMANITY_ROW as usize);
+                for (n, stack) in player.content.iter().enumerate() {
+                    let row = n as i32 + STACK_ROWS;
+                    panel.print(
+                        1,
+                        FIRST_ROW + row,
+                        format!(
+                            " - {} ({}g) {}/{}",
+                            stack.item.description(),
+                            stack.item.gold(),
+                            self.selected.get(&row).unwrap_or(&0),
+                            stack.count
+                        ),
+                    );
+                    self.set_background(panel, FIRST_ROW + row, row as usize);
                 }
             },
         );
@@ -134,7 +271,7 @@ impl Trade {
             (console.width() - 20) / 2 + 12,
             15,
             (console.width() - 20) / 2 - 3,
-            console.height() - 27,
+            console.height() - 31,
             Some("You deserve"),
             |panel, _, _| {
                 let mut wtf_start = 1;
@@ -142,73 +279,186 @@ impl Trade {
                     panel.print(1, 2, "Other life");
                     wtf_start = 2;
                 }
-                for (n, item) in self.deservables.items.iter().enumerate() {
-                    panel.print(1, n as i32 + 1 + wtf_start, item.description());
+                for (n, stack) in self.deservables.items.iter().enumerate() {
+                    panel.print(
+                        1,
+                        n as i32 + 1 + wtf_start,
+                        format!("{} x{}", stack.item.description(), stack.count),
+                    );
+                }
+                if self.deservables.gold_left > 0 {
+                    panel.print(
+                        1,
+                        self.deservables.items.len() as i32 + 1 + wtf_start,
+                        format!("{} gold left over", self.deservables.gold_left),
+                    );
                 }
             },
         );
 
+        panel(
+            console,
+            12,
+            console.height() - 16,
+            console.width() - 24,
+            4,
+            None,
+            |panel, _, _| {
+                panel.print(
+                    1,
+                    1,
+                    format!(
+                        "You offer {} gold. My life costs {} gold, or {} with your life",
+                        self.deservables.gold, self.prices.life, self.prices.life_for_yours
+                    ),
+                );
+                panel.print(
+                    1,
+                    2,
+                    format!(
+                        "My goods cost {} gold in total",
+                        self.prices
+                            .items
+                            .iter()
+                            .fold(0, |sum, (stack, price)| sum + price * stack.count)
+                    ),
+                );
+            },
+        );
+
         console.print(
             36,
             console.height() - 12,
-            "Esc - drop the deal, Enter - sign with the blood",
+            format!(
+                "{} - drop the deal, {} - sign with the blood",
+                keymap.keys_for(Command::Cancel),
+                keymap.keys_for(Command::Confirm)
+            ),
         );
+    }
+
+    /// Takes the offered stacks and the sold humanity from the player.
+    fn pay(&self, player: &mut Object, log: &mut Log) {
+        let offered = self
+            .selected
+            .iter()
+            .filter(|(key, amount)| **key >= STACK_ROWS && **amount > 0)
+            .map(|(key, amount)| {
+                (
+                    player.content.get(stack_index(*key)).unwrap().item.clone(),
+                    *amount,
+                )
+            })
+            .collect::<Vec<_>>();
+        for (item, amount) in offered {
+            player.content.remove(&item, amount);
+        }
+        let humanity = *self.selected.get(&HUMANITY_ROW).unwrap_or(&0);
+        if humanity > 0 {
+            player.humanity -= humanity;
+            log.add(
+                Category::Trade,
+                &format!("You sold {} of your humanity", humanity),
+                colors::RED,
+            );
+        }
+    }
 
-        console.flush();
+    fn deliver(&self, trader: &mut Object, player: &mut Object) {
+        for stack in self.deservables.items.iter() {
+            trader.content.remove(&stack.item, stack.count);
+            player.content.add(stack.item.clone(), stack.count);
+        }
+    }
 
-        let key = console.wait_for_keypress(true);
-        match key {
-            Key { code: Escape, .. } => {
+    pub fn step(
+        &mut self,
+        command: Command,
+        player: &mut Object,
+        objects: &mut Vec<Object>,
+        log: &mut Log,
+        stats: &mut Stats,
+    ) {
+        match command {
+            Command::Cancel => {
                 self.opened = false;
             }
-            Key { code: Enter, .. } => {
+            Command::Confirm => {
                 self.opened = false;
-                if self.selected.get(&0).map_or(false, |x| *x == 1) && self.deservables.life {
-                    std::mem::swap(player, &mut objects[self.magic_index])
-                } else {
+                if self.selected.get(&LIFE_ROW).map_or(false, |x| *x == 1) && self.deservables.life
+                {
+                    self.pay(player, log);
+                    let trader = &mut objects[self.magic_index];
+                    self.deliver(trader, player);
+                    body::swap(player, trader);
+                    log.add(
+                        Category::Trade,
+                        &format!("You wake up as {}", player.description.to_lowercase()),
+                        colors::LIGHT_VIOLET,
+                    );
+                    if player.original {
+                        log.add(Category::Trade, "You are yourself again", colors::GREEN);
+                    }
+                    stats.body_swaps += 1;
+                    stats.trades += 1;
+                } else if self.deservables.life || !self.deservables.items.is_empty() {
+                    self.pay(player, log);
+                    stats.trades += 1;
+                    let trader = &mut objects[self.magic_index];
+                    self.deliver(trader, player);
                     if self.deservables.life {
-                        player.content.push(Item::Life {
-                            kind: objects[self.magic_index].kind,
-                            description: objects[self.magic_index].description.to_string(),
-                        });
-                        std::mem::replace(
-                            &mut objects[self.magic_index],
-                            crate::objects::garbage(),
+                        player.content.add(
+                            Item::Life {
+                                kind: trader.kind,
+                                description: trader.description.to_string(),
+                            },
+                            1,
                         );
+                        stats.lives_taken += 1;
+                        std::mem::replace(trader, crate::objects::garbage());
                     }
-                    for item in &self.deservables.items {
-                        player.content.push(item.clone());
-                    }
+                } else {
+                    log.add(
+                        Category::Trade,
+                        "Nothing you offer is worth a deal",
+                        colors::DARK_GREY,
+                    );
                 }
             }
 
-            Key { code: Up, .. } => {
+            Command::Direction(0, -1) => {
                 if self.selection > 0 {
                     self.selection -= 1;
                 }
             }
-            Key { code: Down, .. } => {
-                if self.selection < player.content.len() {
+            Command::Direction(0, 1) => {
+                if self.selection < Trade::last_row(player) {
                     self.selection += 1;
                 }
             }
-            Key { code: Left, .. } => {
+            Command::Direction(-1, 0) => {
                 let amount = self.selected.entry(self.selection as i32).or_insert(0);
                 if *amount > 0 {
                     *amount -= 1;
-                    self.update_deservables(player, objects);
+                    self.update_deservables(player);
                 }
             }
-            Key { code: Right, .. } => {
+            Command::Direction(1, 0) => {
+                let max = self.selectable(player, self.selection);
                 let amount = self.selected.entry(self.selection as i32).or_insert(0);
-                if *amount == 0 {
+                if *amount < max {
                     *amount += 1;
-                    self.update_deservables(player, objects);
+                    self.update_deservables(player);
                 }
             }
+            Command::Select(row) if row <= Trade::last_row(player) => {
+                self.selection = row;
+                let max = self.selectable(player, row);
+                let amount = self.selected.entry(row as i32).or_insert(0);
+                *amount = if *amount > 0 { 0 } else { max };
+                self.update_deservables(player);
+            }
             _ => {}
         }
-
-        true
     }
 }